@@ -1,3 +1,4 @@
+#[macro_use]
 extern crate intcode;
 use std::cmp::{max, min, Ordering};
 use std::collections::HashMap;
@@ -49,27 +50,26 @@ fn boundaries(points: &Vec<Point>) -> (Point, Point) {
     (Point(min_x, min_y), Point(max_x, max_y))
 }
 
-fn draw_tiles(pdata: String, start: &String) -> HashMap<Point, String> {
+fn draw_tiles(pdata: String, start: i64) -> HashMap<Point, i64> {
     let (tx, rx, _) = spawn(pdata, None);
     let mut cursor = Point(0, 0);
     let mut direction = 0;
-    let mut tiles: HashMap<Point, String> = HashMap::new();
-    tiles.insert(cursor, start.clone());
+    let mut tiles: HashMap<Point, i64> = HashMap::new();
+    tiles.insert(cursor, start);
     loop {
-        let tile = tiles.entry(cursor).or_insert("0".to_string());
-        match tx.send(tile.clone()) {
-            Err(_) => break,
-            Ok(_) => {}
-        };
+        let tile = tiles.entry(cursor).or_insert(0);
+        if tx.send(*tile).is_err() {
+            break;
+        }
         match rx.recv() {
             Err(_) => break,
             Ok(new_tile) => *tile = new_tile,
         };
         match rx.recv() {
             Err(_) => break,
-            Ok(turn) => match turn.as_ref() {
-                "0" => direction = modulo(direction - 1, 4),
-                "1" => direction = modulo(direction + 1, 4),
+            Ok(turn) => match turn {
+                0 => direction = modulo(direction - 1, 4),
+                1 => direction = modulo(direction + 1, 4),
                 _ => unimplemented!(),
             },
         };
@@ -79,7 +79,7 @@ fn draw_tiles(pdata: String, start: &String) -> HashMap<Point, String> {
 }
 
 
-fn write_plate(tiles: &HashMap<Point, String>) {
+fn write_plate(tiles: &HashMap<Point, i64>) {
     let points: Vec<Point> = tiles.iter().map(|(k, _)| *k).collect();
     let (from, to) = boundaries(&points);
     println!("{:?}", (from, to));
@@ -88,9 +88,9 @@ fn write_plate(tiles: &HashMap<Point, String>) {
         let mut row = String::with_capacity(width as usize);
         for c in (from.0)..(to.0)+1 {
             match tiles.get(&Point(c, r)) {
-                Some(t) => match t.as_ref() {
-                    "0" => row.push(' '),
-                    "1" => row.push('#'),
+                Some(t) => match t {
+                    0 => row.push(' '),
+                    1 => row.push('#'),
                     _ => row.push('?')
                 },
                 None => row.push(' '),
@@ -103,10 +103,10 @@ fn write_plate(tiles: &HashMap<Point, String>) {
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
-    let start = &args[2];
+    let start = parse!(&args[2], i64);
     let pdata = read_to_string(filename).expect("cannot read file to string");
     //part1
-    let tiles = draw_tiles(pdata, &start);
+    let tiles = draw_tiles(pdata, start);
     println!("{}", tiles.len());
     write_plate(&tiles);
 }
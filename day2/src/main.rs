@@ -1,10 +1,11 @@
 pub mod intcode;
+pub mod solver;
 
 use std::env;
 use std::io::{self, BufRead};
-use std::mem::replace;
+use std::thread;
 
-use intcode::{create_memory, intcode};
+use intcode::create_memory;
 
 fn main() {
     let stdin = io::stdin();
@@ -15,11 +16,19 @@ fn main() {
         .expect("there was no next line")
         .expect("the line could not be read");
     let args: Vec<String> = env::args().collect();
+    let memory = create_memory(line);
+
+    if args[1] == "--target" {
+        let target: i64 = args[2].parse().unwrap();
+        let threads = thread::available_parallelism().map_or(4, |n| n.get());
+        match solver::search(&memory, target, threads) {
+            Some((noun, verb)) => println!("{}", 100 * noun + verb),
+            None => println!("no noun and verb produce {}", target),
+        }
+        return;
+    }
+
     let noun: i64 = args[1].parse().unwrap();
     let verb: i64 = args[2].parse().unwrap();
-
-    let mut memory = create_memory(line);
-    replace(&mut memory[1], noun);
-    replace(&mut memory[2], verb);
-    println!("{}", intcode(&mut memory));
+    println!("{}", solver::run(&memory, noun, verb));
 }
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::intcode::{intcode, Memory};
+
+/// Runs the program with `noun` and `verb` in addresses 1 and 2.
+pub fn run(memory: &[i64], noun: i64, verb: i64) -> i64 {
+    let mut memory: Memory = memory.to_vec();
+    memory[1] = noun;
+    memory[2] = verb;
+    intcode(&mut memory)
+}
+
+/// Searches all nouns and verbs in `0..=99` for the pair that leaves `target`
+/// in address 0. The nouns are split across `threads` threads.
+pub fn search(memory: &[i64], target: i64, threads: usize) -> Option<(i64, i64)> {
+    // addresses beyond the program would make the interpreter panic
+    let max = (memory.len() as i64 - 1).min(99);
+    let threads = threads.max(1) as i64;
+    let found = Arc::new(AtomicBool::new(false));
+    let workers: Vec<_> = (0..threads)
+        .map(|start| {
+            let memory = memory.to_vec();
+            let found = found.clone();
+            thread::spawn(move || {
+                for noun in (start..=max).step_by(threads as usize) {
+                    for verb in 0..=max {
+                        if found.load(Ordering::Relaxed) {
+                            return None;
+                        }
+                        if run(&memory, noun, verb) == target {
+                            found.store(true, Ordering::Relaxed);
+                            return Some((noun, verb));
+                        }
+                    }
+                }
+                None
+            })
+        })
+        .collect();
+    workers
+        .into_iter()
+        .filter_map(|worker| worker.join().expect("worker panicked"))
+        .min()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::create_memory;
+    #[test]
+    fn test_search() {
+        let memory = create_memory(include_str!("../input").trim().to_string());
+        let (noun, verb) = search(&memory, 19690720, 4).unwrap();
+        assert_eq!(run(&memory, noun, verb), 19690720);
+        assert_eq!(search(&memory, -1, 4), None);
+    }
+}
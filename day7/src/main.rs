@@ -10,51 +10,22 @@ use std::fs;
 
 #[macro_use]
 extern crate intcode;
-use intcode::spawn;
+use intcode::pipeline::{feedback, seeded};
+use intcode::{create_memory, Machine};
 
 fn main() {
     env_logger::init();
     debug!("start");
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
-    let from = parse!(&args[2], usize);
-    let to = parse!(&args[3], usize);
+    let from = parse!(&args[2], i64);
+    let to = parse!(&args[3], i64);
     let data = fs::read_to_string(filename).unwrap();
+    let amp = Machine::new(create_memory(&data).expect("cannot parse program"));
 
     for settings in (from..(to + 1)).permutations(5) {
-        let phase_a = settings[0].to_string();
-        let (tx_to_a, rx_from_a, _) = spawn(data.clone(), Some(phase_a));
-        tx_to_a.send(String::from("0")).unwrap();
-
-        let phase_b = settings[1].to_string();
-        let (tx_to_b, rx_from_b, _) = spawn(data.clone(), Some(phase_b));
-        tx_to_b.send(rx_from_a.recv().unwrap()).unwrap();
-
-        let phase_c = settings[2].to_string();
-        let (tx_to_c, rx_from_c, _) = spawn(data.clone(), Some(phase_c));
-        tx_to_c.send(rx_from_b.recv().unwrap()).unwrap();
-
-        let phase_d = settings[3].to_string();
-        let (tx_to_d, rx_from_d, _) = spawn(data.clone(), Some(phase_d));
-        tx_to_d.send(rx_from_c.recv().unwrap()).unwrap();
-
-        let phase_e = settings[4].to_string();
-        let (tx_to_e, rx_from_e, _) = spawn(data.clone(), Some(phase_e));
-        tx_to_e.send(rx_from_d.recv().unwrap()).unwrap();
-        let mut out = String::new();
-        for recv in rx_from_e {
-            out = recv.clone();
-            //debug!("main: loop: {}", out);
-            match tx_to_a.send(recv) {
-                Ok(_) => {
-                    tx_to_b.send(rx_from_a.recv().unwrap()).unwrap();
-                    tx_to_c.send(rx_from_b.recv().unwrap()).unwrap();
-                    tx_to_d.send(rx_from_c.recv().unwrap()).unwrap();
-                    tx_to_e.send(rx_from_d.recv().unwrap()).unwrap();
-                }
-                Err(_) => {}
-            };
-        }
-        println!("{}", out);
+        let phases: Vec<[i64; 1]> = settings.iter().map(|phase| [*phase]).collect();
+        let out = feedback(seeded(&amp, &phases), &[0]).expect("amplifiers failed");
+        println!("{}", out.unwrap_or(0));
     }
 }
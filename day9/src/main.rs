@@ -1,3 +1,4 @@
+#[macro_use]
 extern crate intcode;
 use intcode::spawn;
 use std::env;
@@ -7,7 +8,7 @@ fn main() {
     let intcode_file = &args[1];
     let mode = &args[2];
     let data = read_to_string(intcode_file).unwrap();
-    let (_tx, rx, _) = spawn(data, Some(mode.clone()));
+    let (_tx, rx, _) = spawn(data, Some(parse!(mode, i64)));
 
     let output = rx.recv().unwrap();
     println!("{}", output);
@@ -0,0 +1,39 @@
+#[macro_use]
+extern crate criterion;
+extern crate intcode;
+
+use criterion::Criterion;
+use intcode::{create_memory, Machine, State};
+
+const BOOST: &str = include_str!("../../day9/input");
+const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+
+fn run(data: &str, input: Option<i64>) -> Vec<i64> {
+    let mut machine = Machine::new(create_memory(data).unwrap());
+    if let Some(value) = input {
+        machine.input(value);
+    }
+    let mut output = Vec::new();
+    loop {
+        match machine.run_until_io().unwrap() {
+            State::Output(value) => output.push(value),
+            State::Input => panic!("program wants more input"),
+            State::Halted => return output,
+        }
+    }
+}
+
+fn boost(c: &mut Criterion) {
+    c.bench_function("day9 boost test mode", |b| b.iter(|| run(BOOST, Some(1))));
+    let mut group = c.benchmark_group("long running");
+    group.sample_size(10);
+    group.bench_function("day9 sensor boost", |b| b.iter(|| run(BOOST, Some(2))));
+    group.finish();
+}
+
+fn quine(c: &mut Criterion) {
+    c.bench_function("relative mode quine", |b| b.iter(|| run(QUINE, None)));
+}
+
+criterion_group!(benches, boost, quine);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+//! Console adapter for programs that read commands and print text as ASCII
+//! character codes.
+use super::{IntcodeError, Machine, State};
+
+/// Everything a program printed until it asked for input or halted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Output {
+    /// Printed text split at newlines, a trailing prompt without newline is
+    /// the last line.
+    pub lines: Vec<String>,
+    /// Values that are not ASCII, above 127 or negative, usually the answer
+    /// of the puzzle.
+    pub values: Vec<i64>,
+}
+
+impl Output {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// Character codes of `text` followed by a newline.
+pub fn encode(text: &str) -> Vec<i64> {
+    text.bytes().chain(Some(b'\n')).map(i64::from).collect()
+}
+
+pub struct Console {
+    machine: Machine,
+}
+
+impl Console {
+    pub fn new(machine: Machine) -> Self {
+        Self { machine }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn halted(&self) -> bool {
+        self.machine.halted()
+    }
+
+    /// Queues `text` plus newline as input.
+    pub fn send(&mut self, text: &str) {
+        for code in encode(text) {
+            self.machine.input(code);
+        }
+    }
+
+    /// Runs until the program waits for more input than was sent or halts.
+    pub fn run(&mut self) -> Result<Output, IntcodeError> {
+        let mut output = Output::default();
+        let mut line = String::new();
+        loop {
+            match self.machine.run_until_io()? {
+                State::Output(10) => output.lines.push(line.split_off(0)),
+                State::Output(value) if (0..128).contains(&value) => line.push(value as u8 as char),
+                State::Output(value) => output.values.push(value),
+                State::Input | State::Halted => break,
+            }
+        }
+        if !line.is_empty() {
+            output.lines.push(line);
+        }
+        Ok(output)
+    }
+
+    /// Sends `text` and returns what the program printed in response.
+    pub fn command(&mut self, text: &str) -> Result<Output, IntcodeError> {
+        self.send(text);
+        self.run()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    // prints a prompt, echoes one line in upper case, then prints its length
+    const SHOUT: &str = "
+                OUT #63
+                OUT #32
+        loop:   IN [c]
+                EQ [c], #10, [tmp]
+                JT [tmp], #done
+                LT [c], #97, [tmp]
+                JT [tmp], #echo
+                ADD [c], #-32, [c]
+        echo:   OUT [c]
+                ADD [len], #1, [len]
+                JT #1, #loop
+        done:   OUT #10
+                ADD [len], #1000, [len]
+                OUT [len]
+                HLT
+        c:      DATA 0
+        len:    DATA 0
+        tmp:    DATA 0
+    ";
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("NOT A J"), vec![78, 79, 84, 32, 65, 32, 74, 10]);
+    }
+
+    #[test]
+    fn test_console() {
+        let mut console = Console::new(Machine::new(assemble(SHOUT).unwrap()));
+        let prompt = console.run().unwrap();
+        assert_eq!(prompt.text(), "? ");
+        assert!(prompt.values.is_empty());
+
+        let output = console.command("walk").unwrap();
+        assert_eq!(output.lines, vec!["WALK".to_string()]);
+        assert_eq!(output.values, vec![1004]);
+        assert!(console.halted());
+    }
+
+    #[test]
+    fn test_values() {
+        let data = crate::create_memory("104,72,104,-5,104,128,104,105,99").unwrap();
+        let output = Console::new(Machine::new(data)).run().unwrap();
+        assert_eq!(output.text(), "Hi");
+        assert_eq!(output.values, vec![-5, 128]);
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use super::disasm::MNEMONICS;
+use super::Memory;
+
+/// Problems found while assembling, `line` is 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    UnknownMnemonic {
+        line: usize,
+        name: String,
+    },
+    OperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    BadOperand {
+        line: usize,
+        text: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        name: String,
+    },
+    UnknownLabel {
+        line: usize,
+        name: String,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, name } => {
+                write!(f, "line {}: unknown mnemonic {:?}", line, name)
+            }
+            AsmError::OperandCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands, found {}",
+                line, expected, found
+            ),
+            AsmError::BadOperand { line, text } => {
+                write!(f, "line {}: cannot parse operand {:?}", line, text)
+            }
+            AsmError::DuplicateLabel { line, name } => {
+                write!(f, "line {}: label {:?} defined twice", line, name)
+            }
+            AsmError::UnknownLabel { line, name } => {
+                write!(f, "line {}: unknown label {:?}", line, name)
+            }
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug)]
+enum Value {
+    Number(i64),
+    Label(String),
+}
+
+#[derive(Debug)]
+struct Operand {
+    mode: i64,
+    value: Value,
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction(u8, Vec<Operand>),
+    Data(Vec<Value>),
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn value(line: usize, text: &str) -> Result<Value, AsmError> {
+    let text = text.trim();
+    if is_label(text) {
+        return Ok(Value::Label(text.to_string()));
+    }
+    let number = text.strip_prefix('+').unwrap_or(text);
+    number
+        .parse()
+        .map(Value::Number)
+        .map_err(|_| AsmError::BadOperand {
+            line,
+            text: text.to_string(),
+        })
+}
+
+fn operand(line: usize, text: &str) -> Result<Operand, AsmError> {
+    let text = text.trim();
+    if let Some(inner) = text.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+        return Ok(Operand {
+            mode: 0,
+            value: value(line, inner)?,
+        });
+    }
+    if let Some(inner) = text.strip_prefix('#') {
+        return Ok(Operand {
+            mode: 1,
+            value: value(line, inner)?,
+        });
+    }
+    if let Some(offset) = text.strip_prefix("rel") {
+        let value = if offset.is_empty() {
+            Value::Number(0)
+        } else if offset.starts_with('+') || offset.starts_with('-') {
+            value(line, offset)?
+        } else {
+            return Err(AsmError::BadOperand {
+                line,
+                text: text.to_string(),
+            });
+        };
+        return Ok(Operand { mode: 2, value });
+    }
+    Err(AsmError::BadOperand {
+        line,
+        text: text.to_string(),
+    })
+}
+
+fn split_list(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    text.split(',').map(|x| x.trim()).collect()
+}
+
+/// Assembles mnemonic source into a memory image.
+///
+/// Every line holds an optional `label:`, then an instruction such as
+/// `ADD [12], #5, rel+3` or a `DATA 1, -2, label` directive. `[x]` is
+/// position, `#x` immediate and `rel+x` relative mode, `x` may be a number
+/// or a label. `;` starts a comment. Numeric labels like `0004:` are
+/// ignored, so the output of `disasm::disassemble` assembles again.
+pub fn assemble(source: &str) -> Result<Memory, AsmError> {
+    let mut labels: HashMap<String, i64> = HashMap::new();
+    let mut items: Vec<(usize, Item)> = Vec::new();
+    let mut addr = 0;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut text = text.split(';').next().unwrap_or("").trim();
+        if let Some(pos) = text.find(':') {
+            let name = text[..pos].trim();
+            if is_label(name) {
+                if labels.insert(name.to_string(), addr).is_some() {
+                    return Err(AsmError::DuplicateLabel {
+                        line,
+                        name: name.to_string(),
+                    });
+                }
+            } else if name.parse::<usize>().is_err() {
+                return Err(AsmError::BadOperand {
+                    line,
+                    text: name.to_string(),
+                });
+            }
+            text = text[pos + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (name, rest) = match text.find(char::is_whitespace) {
+            Some(pos) => (&text[..pos], &text[pos..]),
+            None => (text, ""),
+        };
+        let name = name.to_uppercase();
+        if name == "DATA" {
+            let values = split_list(rest)
+                .into_iter()
+                .map(|x| value(line, x))
+                .collect::<Result<Vec<Value>, AsmError>>()?;
+            addr += values.len() as i64;
+            items.push((line, Item::Data(values)));
+            continue;
+        }
+        let (op, arity) = match MNEMONICS.iter().find(|(_, m, _)| *m == name) {
+            Some((op, _, arity)) => (*op, *arity),
+            None => return Err(AsmError::UnknownMnemonic { line, name }),
+        };
+        let operands = split_list(rest)
+            .into_iter()
+            .map(|x| operand(line, x))
+            .collect::<Result<Vec<Operand>, AsmError>>()?;
+        if operands.len() != arity {
+            return Err(AsmError::OperandCount {
+                line,
+                expected: arity,
+                found: operands.len(),
+            });
+        }
+        addr += arity as i64 + 1;
+        items.push((line, Item::Instruction(op, operands)));
+    }
+
+    let resolve = |line: usize, value: &Value| match value {
+        Value::Number(x) => Ok(*x),
+        Value::Label(name) => labels
+            .get(name)
+            .copied()
+            .ok_or_else(|| AsmError::UnknownLabel {
+                line,
+                name: name.clone(),
+            }),
+    };
+    let mut memory = Memory::with_capacity(addr as usize);
+    for (line, item) in items {
+        match item {
+            Item::Data(values) => {
+                for value in values.iter() {
+                    memory.push(resolve(line, value)?);
+                }
+            }
+            Item::Instruction(op, operands) => {
+                let mut code = op as i64;
+                let mut scale = 100;
+                for operand in operands.iter() {
+                    code += operand.mode * scale;
+                    scale *= 10;
+                }
+                memory.push(code);
+                for operand in operands.iter() {
+                    memory.push(resolve(line, &operand.value)?);
+                }
+            }
+        }
+    }
+    Ok(memory)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disasm::disassemble;
+    use crate::{create_memory, Machine, State};
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; outputs 1 if the input equals 8, 0 otherwise
+                    IN [value]
+                    EQ [value], #8, [value]
+                    OUT [value]
+                    HLT
+            value:  DATA -1
+        ";
+        let memory = assemble(source).unwrap();
+        assert_eq!(memory, create_memory("3,9,1008,9,8,9,4,9,99,-1").unwrap());
+
+        let mut p = Machine::new(memory);
+        p.input(8);
+        assert_eq!(p.run_until_io(), Ok(State::Output(1)));
+    }
+    #[test]
+    fn test_round_trip() {
+        let data = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let memory = create_memory(data).unwrap();
+        assert_eq!(assemble(&disassemble(&memory)).unwrap(), memory);
+
+        let memory = create_memory("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        assert_eq!(assemble(&disassemble(&memory)).unwrap(), memory);
+    }
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("NOP"),
+            Err(AsmError::UnknownMnemonic {
+                line: 1,
+                name: "NOP".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("HLT\nADD #1, #2"),
+            Err(AsmError::OperandCount {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("JT #1, #nowhere"),
+            Err(AsmError::UnknownLabel {
+                line: 1,
+                name: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("OUT @3"),
+            Err(AsmError::BadOperand {
+                line: 1,
+                text: "@3".to_string()
+            })
+        );
+    }
+}
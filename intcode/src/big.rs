@@ -0,0 +1,258 @@
+//! Machine with arbitrary precision words for programs whose values do not
+//! fit into an `i64`. Slower than `Machine`, addresses still have to fit
+//! into the memory size.
+use std::collections::{HashMap, VecDeque};
+
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use super::{instruction, memory, taken, Instruction, IntcodeError};
+
+/// Like `State`, with big outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BigState {
+    Input,
+    Output(BigInt),
+    Halted,
+}
+
+pub fn create_memory(data: &str) -> Result<Vec<BigInt>, IntcodeError> {
+    data.trim()
+        .split(',')
+        .enumerate()
+        .map(|(index, token)| {
+            token.trim().parse().map_err(|_| IntcodeError::Parse {
+                index,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub struct BigMachine {
+    memory: HashMap<usize, BigInt>,
+    input: VecDeque<BigInt>,
+    ptr: usize,
+    rel: BigInt,
+    halted: bool,
+    instructions: u64,
+}
+
+impl BigMachine {
+    pub fn new(memory: Vec<BigInt>) -> Self {
+        Self {
+            memory: memory.into_iter().enumerate().collect(),
+            input: VecDeque::new(),
+            ptr: 0,
+            rel: BigInt::zero(),
+            halted: false,
+            instructions: 0,
+        }
+    }
+
+    pub fn input<V: Into<BigInt>>(&mut self, value: V) {
+        self.input.push_back(value.into());
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn peek(&self, addr: usize) -> BigInt {
+        self.memory.get(&addr).cloned().unwrap_or_default()
+    }
+
+    fn code(&self) -> i64 {
+        self.peek(self.ptr).to_i64().unwrap_or(i64::MAX)
+    }
+
+    fn address(&self, mode: u32, offset: usize) -> Result<usize, IntcodeError> {
+        let param = self.peek(self.ptr + offset);
+        let addr = match mode {
+            0 => param,
+            1 => BigInt::from(self.ptr + offset),
+            2 => &self.rel + param,
+            _ => {
+                return Err(IntcodeError::UnknownMode {
+                    ptr: self.ptr,
+                    code: self.code(),
+                    operand: offset,
+                    mode,
+                })
+            }
+        };
+        if addr < BigInt::zero() {
+            return Err(IntcodeError::NegativeAddress {
+                ptr: self.ptr,
+                code: self.code(),
+                operand: offset,
+                addr: addr.to_i64().unwrap_or(i64::MIN),
+            });
+        }
+        match addr.to_usize() {
+            Some(addr) if addr < memory::DEFAULT_SIZE => Ok(addr),
+            _ => Err(IntcodeError::OutOfMemory {
+                ptr: self.ptr,
+                code: self.code(),
+                operand: offset,
+                addr: addr.to_usize().unwrap_or(usize::MAX),
+                size: memory::DEFAULT_SIZE,
+            }),
+        }
+    }
+
+    fn param(&self, mode: u32, offset: usize) -> Result<BigInt, IntcodeError> {
+        Ok(self.peek(self.address(mode, offset)?))
+    }
+
+    fn store(&mut self, mode: u32, offset: usize, value: BigInt) -> Result<(), IntcodeError> {
+        let addr = self.address(mode, offset)?;
+        self.memory.insert(addr, value);
+        Ok(())
+    }
+
+    /// Jumps with the rules of `Machine`, decided on the sign of `ma`.
+    fn jump(&mut self, on_true: bool, ma: u32, mb: u32) -> Result<usize, IntcodeError> {
+        let sign = self.param(ma, 1)?.signum().to_i64().unwrap_or(0);
+        if !taken(on_true, sign) {
+            return Ok(self.ptr + 3);
+        }
+        let target = self.param(mb, 2)?;
+        target
+            .to_usize()
+            .ok_or_else(|| IntcodeError::NegativeAddress {
+                ptr: self.ptr,
+                code: self.code(),
+                operand: 2,
+                addr: target.to_i64().unwrap_or(i64::MIN),
+            })
+    }
+
+    /// Executes a single instruction, returns the state if it stopped the machine.
+    pub fn step(&mut self) -> Result<Option<BigState>, IntcodeError> {
+        if self.halted {
+            return Ok(Some(BigState::Halted));
+        }
+        let code = self.code();
+        let inst = instruction(code).ok_or(IntcodeError::UnknownOpcode {
+            ptr: self.ptr,
+            code,
+        })?;
+        let mut state = None;
+        let next = match inst {
+            Instruction::Add(ma, mb, mc) => {
+                let value = self.param(ma, 1)? + self.param(mb, 2)?;
+                self.store(mc, 3, value)?;
+                self.ptr + 4
+            }
+            Instruction::Mul(ma, mb, mc) => {
+                let value = self.param(ma, 1)? * self.param(mb, 2)?;
+                self.store(mc, 3, value)?;
+                self.ptr + 4
+            }
+            Instruction::In(ma) => match self.input.pop_front() {
+                Some(value) => {
+                    self.store(ma, 1, value)?;
+                    self.ptr + 2
+                }
+                None => {
+                    self.address(ma, 1)?;
+                    return Ok(Some(BigState::Input));
+                }
+            },
+            Instruction::Out(ma) => {
+                state = Some(BigState::Output(self.param(ma, 1)?));
+                self.ptr + 2
+            }
+            Instruction::JumpTrue(ma, mb) => self.jump(true, ma, mb)?,
+            Instruction::JumpFalse(ma, mb) => self.jump(false, ma, mb)?,
+            Instruction::LessThan(ma, mb, mc) => {
+                let value = self.param(ma, 1)? < self.param(mb, 2)?;
+                self.store(mc, 3, BigInt::from(value as i64))?;
+                self.ptr + 4
+            }
+            Instruction::Equals(ma, mb, mc) => {
+                let value = self.param(ma, 1)? == self.param(mb, 2)?;
+                self.store(mc, 3, BigInt::from(value as i64))?;
+                self.ptr + 4
+            }
+            Instruction::AdjustRel(ma) => {
+                self.rel += self.param(ma, 1)?;
+                self.ptr + 2
+            }
+            Instruction::Halt => {
+                self.halted = true;
+                state = Some(BigState::Halted);
+                self.ptr
+            }
+        };
+        self.instructions += 1;
+        self.ptr = next;
+        Ok(state)
+    }
+
+    /// Runs until the program needs input, produces output or halts.
+    pub fn run_until_io(&mut self) -> Result<BigState, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_big_words() {
+        // squares the input twice
+        let data = "3,13,2,13,13,13,2,13,13,13,4,13,99,0";
+        let mut p = BigMachine::new(create_memory(data).unwrap());
+        p.input(4_294_967_296i64);
+        let expected: BigInt = "340282366920938463463374607431768211456".parse().unwrap();
+        assert_eq!(p.run_until_io(), Ok(BigState::Output(expected)));
+        assert_eq!(p.run_until_io(), Ok(BigState::Halted));
+        assert_eq!(p.instructions(), 5);
+    }
+
+    #[test]
+    fn test_big_program() {
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut p = BigMachine::new(create_memory(quine).unwrap());
+        let mut output = Vec::new();
+        while let BigState::Output(value) = p.run_until_io().unwrap() {
+            output.push(value.to_string());
+        }
+        assert_eq!(output.join(","), quine);
+    }
+
+    #[test]
+    fn test_same_jumps() {
+        use crate::{Machine, State};
+        let programs = [
+            "1105,-1,4,99,104,7,99",
+            "1105,0,4,99,104,7,99",
+            "1105,3,4,99,104,7,99",
+            "1106,-1,4,99,104,7,99",
+            "1106,0,4,99,104,7,99",
+        ];
+        for data in programs.iter() {
+            let mut p = Machine::new(crate::create_memory(data).unwrap());
+            let mut expected = Vec::new();
+            while let State::Output(value) = p.run_until_io().unwrap() {
+                expected.push(BigInt::from(value));
+            }
+            let mut p = BigMachine::new(create_memory(data).unwrap());
+            let mut output = Vec::new();
+            while let BigState::Output(value) = p.run_until_io().unwrap() {
+                output.push(value);
+            }
+            assert_eq!(output, expected, "{}", data);
+        }
+    }
+}
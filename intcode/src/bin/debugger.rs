@@ -0,0 +1,23 @@
+extern crate env_logger;
+extern crate intcode;
+
+use std::env;
+use std::fs::read_to_string;
+use std::io;
+
+use intcode::create_memory;
+use intcode::debugger::Debugger;
+use intcode::Machine;
+
+fn main() {
+    env_logger::init();
+    let args: Vec<String> = env::args().collect();
+    let filename = &args[1];
+    let data = read_to_string(filename).expect("cannot read file to string");
+    let memory = create_memory(&data).expect("cannot parse program");
+    let mut debugger = Debugger::new(Machine::new(memory));
+    let stdin = io::stdin();
+    debugger
+        .repl(stdin.lock(), io::stdout())
+        .expect("cannot talk to terminal");
+}
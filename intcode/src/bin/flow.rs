@@ -0,0 +1,15 @@
+extern crate intcode;
+
+use std::env;
+use std::fs::read_to_string;
+
+use intcode::create_memory;
+use intcode::flow::analyze;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let filename = &args[1];
+    let data = read_to_string(filename).expect("cannot read file to string");
+    let memory = create_memory(&data).expect("cannot parse program");
+    println!("{}", analyze(&memory).to_dot());
+}
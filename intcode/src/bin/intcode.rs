@@ -0,0 +1,212 @@
+extern crate env_logger;
+extern crate intcode;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{read_to_string, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::process;
+
+use intcode::ascii::encode;
+use intcode::session::Session;
+use intcode::{create_memory, IntcodeError, Machine, State};
+
+const USAGE: &str = "usage: intcode [--ascii] [--trace <file>] [--limit <instructions>] \
+                     [--profile] [--dump] [--record <file> | --replay <file>] <program> [input..]
+
+runs an intcode program, inputs are taken from the command line first and then
+read from stdin one per line, outputs are printed one per line
+
+  -a, --ascii     inputs are lines of text, outputs are printed as characters,
+                  values above 127 and negative ones on a line of their own
+  -t, --trace     write a JSON record per executed instruction to <file>
+  -l, --limit     stop after <instructions> instructions
+  -p, --profile   print execution counts and the hottest loops to stderr at halt
+  -d, --dump      print the memory as comma separated values after the run
+  -r, --record    write every consumed input, output and the halt to <file>
+      --replay    feed the inputs recorded in <file> to the program and stop at
+                  the first output that differs from the recording";
+
+struct Options {
+    ascii: bool,
+    trace: Option<String>,
+    limit: Option<u64>,
+    profile: bool,
+    dump: bool,
+    record: Option<String>,
+    replay: Option<String>,
+    program: String,
+    inputs: Vec<String>,
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2)
+}
+
+fn options() -> Options {
+    let mut options = Options {
+        ascii: false,
+        trace: None,
+        limit: None,
+        profile: false,
+        dump: false,
+        record: None,
+        replay: None,
+        program: String::new(),
+        inputs: Vec::new(),
+    };
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" | "--ascii" => options.ascii = true,
+            "-p" | "--profile" => options.profile = true,
+            "-d" | "--dump" => options.dump = true,
+            "-t" | "--trace" => match args.next() {
+                Some(path) => options.trace = Some(path),
+                None => usage("--trace needs a file"),
+            },
+            "-r" | "--record" => match args.next() {
+                Some(path) => options.record = Some(path),
+                None => usage("--record needs a file"),
+            },
+            "--replay" => match args.next() {
+                Some(path) => options.replay = Some(path),
+                None => usage("--replay needs a session file"),
+            },
+            "-l" | "--limit" => match args.next().and_then(|x| x.parse().ok()) {
+                Some(count) => options.limit = Some(count),
+                None => usage("--limit needs a number of instructions"),
+            },
+            "-h" | "--help" => usage("intcode runner"),
+            "--" => positional.extend(&mut args),
+            _ if arg.starts_with('-') && arg.parse::<i64>().is_err() => {
+                usage(&format!("unknown option {}", arg))
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if options.record.is_some() && options.replay.is_some() {
+        usage("--record and --replay exclude each other");
+    }
+    if positional.is_empty() {
+        usage("missing program");
+    }
+    options.program = positional.remove(0);
+    options.inputs = positional;
+    options
+}
+
+/// Queues the next input line, from the command line first, then stdin.
+fn feed(
+    machine: &mut Machine,
+    options: &Options,
+    args: &mut VecDeque<String>,
+    stdin: &mut dyn BufRead,
+    index: &mut usize,
+) -> Result<bool, IntcodeError> {
+    let line = match args.pop_front() {
+        Some(line) => line,
+        None => {
+            let mut line = String::new();
+            if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+                return Ok(false);
+            }
+            line.trim_end_matches(&['\r', '\n'][..]).to_string()
+        }
+    };
+    if options.ascii {
+        for code in encode(&line) {
+            machine.input(code);
+        }
+    } else {
+        let value = line.trim().parse().map_err(|_| IntcodeError::Parse {
+            index: *index,
+            token: line.clone(),
+        })?;
+        machine.input(value);
+    }
+    *index += 1;
+    Ok(true)
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let data = read_to_string(&options.program)?;
+    let mut machine = Machine::new(create_memory(&data)?);
+    if let Some(path) = options.trace.as_ref() {
+        machine.trace(BufWriter::new(File::create(path)?));
+    }
+    if let Some(count) = options.limit {
+        machine.limit_instructions(count);
+    }
+    if options.profile {
+        machine.profile();
+    }
+    if let Some(path) = options.record.as_ref() {
+        machine.record(BufWriter::new(File::create(path)?));
+    }
+    if let Some(path) = options.replay.as_ref() {
+        let session = Session::load(path)?;
+        session.replay(&mut machine)?;
+        eprintln!("{} events replayed", session.events.len());
+        return Ok(());
+    }
+
+    let mut args: VecDeque<String> = options.inputs.iter().cloned().collect();
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut index = 0;
+    let mut column = 0;
+    loop {
+        match machine.run_until_io() {
+            Ok(State::Input) => {
+                out.flush()?;
+                if !feed(&mut machine, options, &mut args, &mut stdin, &mut index)? {
+                    return Err("input closed while the program waits for input".into());
+                }
+            }
+            Ok(State::Output(value)) if options.ascii && (0..128).contains(&value) => {
+                write!(out, "{}", value as u8 as char)?;
+                column = if value == 10 { 0 } else { column + 1 };
+            }
+            Ok(State::Output(value)) => {
+                if column > 0 {
+                    writeln!(out)?;
+                    column = 0;
+                }
+                writeln!(out, "{}", value)?;
+            }
+            Ok(State::Halted) => break,
+            Err(IntcodeError::LimitExceeded { .. }) if options.dump => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if column > 0 {
+        writeln!(out)?;
+    }
+    if let Some(profile) = machine.profiler() {
+        eprintln!("{}", profile.report(machine.memory(), 10));
+    }
+    if options.dump {
+        let exit = machine.exit().expect("stopped machine has an exit");
+        let words: Vec<String> = exit.memory.to_vec().iter().map(|x| x.to_string()).collect();
+        eprintln!(
+            "{:?} after {} instructions at {:04}, rel: {}",
+            exit.reason, exit.instructions, exit.ptr, exit.rel
+        );
+        writeln!(out, "{}", words.join(","))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    env_logger::init();
+    let options = options();
+    if let Err(err) = run(&options) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+use super::disasm::{decode, MNEMONICS};
+use super::{opcode, IntcodeError, Machine, State};
+
+/// Most words `mem` prints and instructions `list` decodes at once.
+const MAX_LEN: i64 = 1024;
+
+/// Why the debugger handed control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// About to execute the instruction at a breakpoint address.
+    Breakpoint(usize),
+    /// About to execute an instruction with a watched opcode.
+    Opcode(usize, u8),
+    /// A watched memory cell changed.
+    Watchpoint { addr: usize, old: i64, new: i64 },
+    /// The machine stopped on its own.
+    Machine(State),
+}
+
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    opcodes: BTreeSet<u8>,
+    watchpoints: BTreeMap<usize, i64>,
+    resume: bool,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            breakpoints: BTreeSet::new(),
+            opcodes: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            resume: false,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn break_at(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn break_on(&mut self, op: u8) {
+        self.opcodes.insert(op);
+    }
+
+    pub fn watch(&mut self, addr: usize) {
+        let value = self.cell(addr);
+        self.watchpoints.insert(addr, value);
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.opcodes.clear();
+        self.watchpoints.clear();
+    }
+
+    fn cell(&self, addr: usize) -> i64 {
+        self.machine.memory.get(addr)
+    }
+
+    fn check_break(&mut self) -> Option<Event> {
+        if self.resume || self.machine.halted() {
+            return None;
+        }
+        let ptr = self.machine.ptr;
+        if self.breakpoints.contains(&ptr) {
+            return Some(Event::Breakpoint(ptr));
+        }
+        let (op, _) = opcode(self.machine.code());
+        if self.opcodes.contains(&op) {
+            return Some(Event::Opcode(ptr, op));
+        }
+        None
+    }
+
+    fn check_watch(&mut self) -> Option<Event> {
+        let mut event = None;
+        for (addr, old) in self.watchpoints.iter_mut() {
+            let new = self.machine.memory.get(*addr);
+            if new != *old {
+                event = event.or(Some(Event::Watchpoint {
+                    addr: *addr,
+                    old: *old,
+                    new,
+                }));
+                *old = new;
+            }
+        }
+        event
+    }
+
+    /// Executes one instruction regardless of breakpoints.
+    pub fn step(&mut self) -> Result<Option<Event>, IntcodeError> {
+        self.resume = false;
+        let state = self.machine.step()?;
+        if let Some(event) = self.check_watch() {
+            return Ok(Some(event));
+        }
+        Ok(state.map(Event::Machine))
+    }
+
+    /// Runs until a breakpoint, a watchpoint or any machine state.
+    pub fn cont(&mut self) -> Result<Event, IntcodeError> {
+        loop {
+            if let Some(event) = self.check_break() {
+                self.resume = true;
+                return Ok(event);
+            }
+            if let Some(event) = self.step()? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn show(&self, out: &mut dyn Write, event: &Event) -> io::Result<()> {
+        match event {
+            Event::Breakpoint(addr) => writeln!(out, "breakpoint at {:04}", addr)?,
+            Event::Opcode(addr, op) => writeln!(out, "opcode {} at {:04}", op, addr)?,
+            Event::Watchpoint { addr, old, new } => {
+                writeln!(out, "watch [{}]: {} -> {}", addr, old, new)?
+            }
+            Event::Machine(State::Input) => writeln!(out, "waiting for input")?,
+            Event::Machine(State::Output(value)) => writeln!(out, "output: {}", value)?,
+            Event::Machine(State::Halted) => writeln!(out, "halted")?,
+        }
+        self.location(out)
+    }
+
+    /// Text and length of the instruction at `addr`.
+    fn decode(&self, addr: usize) -> (String, usize) {
+        let window = self.machine.memory.range(addr, addr + 4);
+        decode(&window, 0).unwrap_or_else(|| (format!("DATA {}", window[0]), 1))
+    }
+
+    fn location(&self, out: &mut dyn Write) -> io::Result<()> {
+        let ptr = self.machine.ptr;
+        writeln!(out, "{:04}: {}", ptr, self.decode(ptr).0)
+    }
+
+    fn list(&self, out: &mut dyn Write, count: usize) -> io::Result<()> {
+        let mut addr = self.machine.ptr;
+        for _ in 0..count {
+            if addr >= self.machine.memory.len() {
+                break;
+            }
+            let (text, len) = self.decode(addr);
+            writeln!(out, "{:04}: {}", addr, text)?;
+            addr += len;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, out: &mut dyn Write, line: &str) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args: Vec<i64> = words
+            .iter()
+            .skip(1)
+            .filter_map(|x| x.parse().ok())
+            .collect();
+        if args.len() + 1 < words.len() && !matches!(words[0], "bo" | "opcode") {
+            writeln!(out, "arguments must be numbers")?;
+            return Ok(true);
+        }
+        let arg = |i: usize, default: i64| args.get(i).copied().unwrap_or(default);
+        let command = words.first().copied().unwrap_or("");
+        let addresses = matches!(command, "b" | "break" | "w" | "watch" | "x" | "mem");
+        if addresses && args.iter().any(|x| *x < 0) {
+            writeln!(out, "addresses and lengths must not be negative")?;
+            return Ok(true);
+        }
+        match command {
+            "" => {}
+            "s" | "step" => {
+                for _ in 0..arg(0, 1) {
+                    match self.step() {
+                        Ok(Some(event)) => {
+                            self.show(out, &event)?;
+                            return Ok(true);
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            writeln!(out, "error: {}", err)?;
+                            return Ok(true);
+                        }
+                    }
+                }
+                self.location(out)?;
+            }
+            "c" | "continue" => loop {
+                match self.cont() {
+                    Ok(Event::Machine(State::Output(value))) => writeln!(out, "output: {}", value)?,
+                    Ok(event) => {
+                        self.show(out, &event)?;
+                        break;
+                    }
+                    Err(err) => {
+                        writeln!(out, "error: {}", err)?;
+                        break;
+                    }
+                }
+            },
+            "b" | "break" if !args.is_empty() => {
+                for addr in args.iter() {
+                    self.break_at(*addr as usize);
+                }
+            }
+            "bo" | "opcode" if words.len() > 1 => {
+                for name in words.iter().skip(1) {
+                    let op = MNEMONICS
+                        .iter()
+                        .find(|(_, m, _)| m.eq_ignore_ascii_case(name))
+                        .map(|(op, _, _)| *op)
+                        .or_else(|| name.parse().ok());
+                    match op {
+                        Some(op) => self.break_on(op),
+                        None => writeln!(out, "unknown opcode {}", name)?,
+                    }
+                }
+            }
+            "w" | "watch" if !args.is_empty() => {
+                for addr in args.iter() {
+                    self.watch(*addr as usize);
+                }
+            }
+            "clear" => self.clear(),
+            "i" | "input" => {
+                for value in args.iter() {
+                    self.machine.input(*value);
+                }
+            }
+            "r" | "regs" => {
+                writeln!(
+                    out,
+                    "ptr: {}, rel: {}, instructions: {}, queued input: {:?}",
+                    self.machine.ptr,
+                    self.machine.rel,
+                    self.machine.instructions,
+                    self.machine.input
+                )?;
+                self.location(out)?;
+            }
+            "x" | "mem" if !args.is_empty() => {
+                let from = args[0] as usize;
+                let to = from + arg(1, 1).clamp(1, MAX_LEN) as usize;
+                let cells: Vec<String> = (from..to).map(|a| self.cell(a).to_string()).collect();
+                writeln!(out, "{:04}: {}", from, cells.join(","))?;
+            }
+            "l" | "list" => self.list(out, arg(0, 10).clamp(0, MAX_LEN) as usize)?,
+            "info" => {
+                writeln!(out, "breakpoints: {:?}", self.breakpoints)?;
+                writeln!(out, "opcodes: {:?}", self.opcodes)?;
+                writeln!(out, "watchpoints: {:?}", self.watchpoints)?;
+            }
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(
+                out,
+                "commands: step [n], continue, break <addr>.., opcode <op>.., watch <addr>.., \
+                 clear, info, input <value>.., regs, mem <addr> [len], list [n], quit"
+            )?,
+        }
+        Ok(true)
+    }
+
+    /// Reads commands line by line from `input` until `quit` or end of input.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        self.location(&mut output)?;
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            if !self.command(&mut output, &line?)? {
+                break;
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_memory;
+    use std::io::Cursor;
+
+    fn debugger(data: &str) -> Debugger {
+        Debugger::new(Machine::new(create_memory(data).unwrap()))
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut d = debugger("3,9,8,9,10,9,4,9,99,-1,8");
+        d.break_at(6);
+        d.break_on(99);
+        d.machine.input(8);
+        assert_eq!(d.cont(), Ok(Event::Breakpoint(6)));
+        assert_eq!(d.cont(), Ok(Event::Machine(State::Output(1))));
+        assert_eq!(d.cont(), Ok(Event::Opcode(8, 99)));
+        assert_eq!(d.cont(), Ok(Event::Machine(State::Halted)));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut d = debugger("1001,9,1,9,1105,1,0,99,0,0");
+        d.watch(9);
+        assert_eq!(
+            d.cont(),
+            Ok(Event::Watchpoint {
+                addr: 9,
+                old: 0,
+                new: 1
+            })
+        );
+        assert_eq!(
+            d.cont(),
+            Ok(Event::Watchpoint {
+                addr: 9,
+                old: 1,
+                new: 2
+            })
+        );
+        assert_eq!(d.machine().instructions(), 3);
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut d = debugger("3,9,8,9,10,9,4,9,99,-1,8");
+        let commands = "step\nb 8\nc\ni 8\nc\nr\nx 9 2\nc\nq\n";
+        let mut out = Vec::new();
+        d.repl(Cursor::new(commands), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let expected = "0000: IN [9]\n\
+                        > waiting for input\n\
+                        0000: IN [9]\n\
+                        > > waiting for input\n\
+                        0000: IN [9]\n\
+                        > > output: 1\n\
+                        breakpoint at 0008\n\
+                        0008: HLT\n\
+                        > ptr: 8, rel: 0, instructions: 3, queued input: []\n\
+                        0008: HLT\n\
+                        > 0009: 1,8\n\
+                        > halted\n\
+                        0008: HLT\n\
+                        > ";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_repl_bounds() {
+        let mut d = debugger("104,7,99");
+        let commands = "b -1
+w 2 -3
+x -1
+x 1 1000000000000
+l -5
+info
+";
+        let mut out = Vec::new();
+        d.repl(Cursor::new(commands), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[1], "> addresses and lengths must not be negative");
+        assert_eq!(lines[2], "> addresses and lengths must not be negative");
+        assert_eq!(lines[3], "> addresses and lengths must not be negative");
+        let cells = lines[4].trim_start_matches("> 0001: ").split(',').count();
+        assert_eq!(cells, MAX_LEN as usize);
+        assert_eq!(lines[5], "> > breakpoints: {}");
+        assert_eq!(lines[7], "watchpoints: {}");
+    }
+}
@@ -0,0 +1,123 @@
+use super::{mode, opcode};
+
+/// Opcode, mnemonic and number of parameters of every instruction.
+pub(crate) const MNEMONICS: [(u8, &str, usize); 10] = [
+    (1, "ADD", 3),
+    (2, "MUL", 3),
+    (3, "IN", 1),
+    (4, "OUT", 1),
+    (5, "JT", 2),
+    (6, "JF", 2),
+    (7, "LT", 3),
+    (8, "EQ", 3),
+    (9, "ARB", 1),
+    (99, "HLT", 0),
+];
+
+fn mnemonic(op: u8) -> Option<(&'static str, usize)> {
+    MNEMONICS
+        .iter()
+        .find(|(code, _, _)| *code == op)
+        .map(|(_, name, arity)| (*name, *arity))
+}
+
+fn operand(mode: u32, value: i64) -> Option<String> {
+    match mode {
+        0 => Some(format!("[{}]", value)),
+        1 => Some(format!("#{}", value)),
+        2 if value < 0 => Some(format!("rel{}", value)),
+        2 => Some(format!("rel+{}", value)),
+        _ => None,
+    }
+}
+
+/// Decodes the instruction at `addr` into its text and length in words,
+/// `None` if the word there is not a valid instruction.
+pub fn decode(memory: &[i64], addr: usize) -> Option<(String, usize)> {
+    let code = *memory.get(addr)?;
+    if code <= 0 {
+        return None;
+    }
+    let (op, modes) = opcode(code);
+    let (name, arity) = mnemonic(op)?;
+    if modes >= 10i64.pow(arity as u32) || addr + arity >= memory.len() {
+        return None;
+    }
+    let mut operands = Vec::with_capacity(arity);
+    for i in 1..=arity {
+        operands.push(operand(mode(modes, i as u32), memory[addr + i])?);
+    }
+    if operands.is_empty() {
+        return Some((name.to_string(), 1));
+    }
+    Some((format!("{} {}", name, operands.join(", ")), arity + 1))
+}
+
+/// Renders a linear listing of `memory`, one instruction per line prefixed
+/// with its address. Runs of words that do not decode become `DATA` lines.
+pub fn disassemble(memory: &[i64]) -> String {
+    let mut lines = Vec::new();
+    let mut data: Vec<i64> = Vec::new();
+    let mut start = 0;
+    let mut addr = 0;
+    while addr < memory.len() {
+        match decode(memory, addr) {
+            Some((text, len)) => {
+                if !data.is_empty() {
+                    lines.push(data_line(start, &data));
+                    data.clear();
+                }
+                lines.push(format!("{:04}: {}", addr, text));
+                addr += len;
+            }
+            None => {
+                if data.is_empty() {
+                    start = addr;
+                }
+                data.push(memory[addr]);
+                addr += 1;
+            }
+        }
+    }
+    if !data.is_empty() {
+        lines.push(data_line(start, &data));
+    }
+    lines.join("\n")
+}
+
+fn data_line(addr: usize, data: &[i64]) -> String {
+    let words: Vec<String> = data.iter().map(|x| x.to_string()).collect();
+    format!("{:04}: DATA {}", addr, words.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_memory;
+    #[test]
+    fn test_decode() {
+        let memory = create_memory("21001,12,5,3").unwrap();
+        assert_eq!(
+            decode(&memory, 0),
+            Some(("ADD [12], #5, rel+3".to_string(), 4))
+        );
+        let memory = create_memory("204,-1,99").unwrap();
+        assert_eq!(decode(&memory, 0), Some(("OUT rel-1".to_string(), 2)));
+        assert_eq!(decode(&memory, 2), Some(("HLT".to_string(), 1)));
+        assert_eq!(decode(&memory, 1), None);
+        let memory = create_memory("301,1,1,1").unwrap();
+        assert_eq!(decode(&memory, 0), None);
+        let memory = create_memory("1,1,1").unwrap();
+        assert_eq!(decode(&memory, 0), None);
+    }
+    #[test]
+    fn test_disassemble() {
+        let memory = create_memory("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        let expected = "0000: IN [9]\n\
+                        0002: EQ [9], [10], [9]\n\
+                        0006: OUT [9]\n\
+                        0008: HLT\n\
+                        0009: DATA -1, 8";
+        assert_eq!(disassemble(&memory), expected);
+    }
+}
@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fmt;
+
+use super::session::Event;
+use super::{Limit, State};
+
+/// Everything that can stop an intcode program other than opcode 99.
+///
+/// Runtime faults carry the instruction pointer and the raw opcode of the
+/// instruction that failed, operand errors additionally the 1-based operand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    /// A program word or a text input line is not an integer, `index` counts
+    /// from the start of the program or of the input stream.
+    Parse {
+        index: usize,
+        token: String,
+    },
+    UnknownOpcode {
+        ptr: usize,
+        code: i64,
+    },
+    UnknownMode {
+        ptr: usize,
+        code: i64,
+        operand: usize,
+        mode: u32,
+    },
+    NegativeAddress {
+        ptr: usize,
+        code: i64,
+        operand: usize,
+        addr: i64,
+    },
+    /// The operand resolved to an address beyond the memory limit.
+    OutOfMemory {
+        ptr: usize,
+        code: i64,
+        operand: usize,
+        addr: usize,
+        size: usize,
+    },
+    /// The machine ran into an instruction or time limit at `ptr`.
+    LimitExceeded {
+        ptr: usize,
+        limit: Limit,
+    },
+    /// Line `line` of a snapshot could not be read.
+    BadSnapshot {
+        line: usize,
+        text: String,
+    },
+    /// Line `line` of a session file could not be read.
+    BadSession {
+        line: usize,
+        text: String,
+    },
+    /// Replaying a session, the machine did `found` where event `event`
+    /// (1-based) of the session expected `expected`.
+    Replay {
+        event: usize,
+        expected: Event,
+        found: State,
+    },
+    /// The input channel hung up while an `In` instruction was waiting.
+    InputClosed {
+        ptr: usize,
+        code: i64,
+    },
+    /// `Add`, `Mul`, `ARB` or a relative mode address overflowed in
+    /// `Arithmetic::Checked` mode.
+    Overflow {
+        ptr: usize,
+        code: i64,
+        a: i64,
+        b: i64,
+    },
+    /// The output channel hung up before `value` could be sent.
+    OutputClosed {
+        ptr: usize,
+        code: i64,
+        value: i64,
+    },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::Parse { index, token } => {
+                write!(f, "cannot parse token {}: {:?}", index, token)
+            }
+            IntcodeError::UnknownOpcode { ptr, code } => {
+                write!(f, "{:04}: unknown opcode {}", ptr, code)
+            }
+            IntcodeError::UnknownMode {
+                ptr,
+                code,
+                operand,
+                mode,
+            } => write!(
+                f,
+                "{:04}: unknown mode {} for operand {} of {}",
+                ptr, mode, operand, code
+            ),
+            IntcodeError::NegativeAddress {
+                ptr,
+                code,
+                operand,
+                addr,
+            } => write!(
+                f,
+                "{:04}: negative address {} in operand {} of {}",
+                ptr, addr, operand, code
+            ),
+            IntcodeError::OutOfMemory {
+                ptr,
+                code,
+                operand,
+                addr,
+                size,
+            } => write!(
+                f,
+                "{:04}: address {} in operand {} of {} exceeds memory size {}",
+                ptr, addr, operand, code, size
+            ),
+            IntcodeError::LimitExceeded { ptr, limit } => match limit {
+                Limit::Instructions(count) => {
+                    write!(f, "{:04}: stopped after {} instructions", ptr, count)
+                }
+                Limit::Time(duration) => write!(f, "{:04}: stopped after {:?}", ptr, duration),
+            },
+            IntcodeError::BadSnapshot { line, text } => {
+                write!(f, "snapshot line {}: cannot read {:?}", line, text)
+            }
+            IntcodeError::BadSession { line, text } => {
+                write!(f, "session line {}: cannot read {:?}", line, text)
+            }
+            IntcodeError::Replay {
+                event,
+                expected,
+                found,
+            } => {
+                let found = match found {
+                    State::Input => "waits for input".to_string(),
+                    State::Output(value) => format!("outputs {}", value),
+                    State::Halted => "halted".to_string(),
+                };
+                write!(
+                    f,
+                    "session event {}: expected `{}`, the machine {}",
+                    event, expected, found
+                )
+            }
+            IntcodeError::Overflow { ptr, code, a, b } => write!(
+                f,
+                "{:04}: {} overflows with operands {} and {}",
+                ptr, code, a, b
+            ),
+            IntcodeError::InputClosed { ptr, code } => {
+                write!(f, "{:04}: input closed while executing {}", ptr, code)
+            }
+            IntcodeError::OutputClosed { ptr, code, value } => write!(
+                f,
+                "{:04}: output closed while executing {}, lost {}",
+                ptr, code, value
+            ),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,330 @@
+//! Static control flow analysis. Follows the program from address 0 through
+//! immediate mode `JT`/`JF` targets and splits it into basic blocks.
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::disasm::decode;
+use super::{instruction, taken, Instruction};
+
+/// A run of instructions that is only entered at `start` and only left after
+/// its last instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// Address and text of every instruction.
+    pub instructions: Vec<(usize, String)>,
+    /// Immediate mode jump target of the last instruction.
+    pub jump: Option<usize>,
+    /// Address execution falls through to.
+    pub next: Option<usize>,
+    /// The last instruction jumps to an address read from memory.
+    pub indirect: bool,
+    /// Address of a word the block runs into that is not a valid instruction.
+    pub invalid: Option<usize>,
+}
+
+impl Block {
+    fn new(start: usize) -> Self {
+        Self {
+            start,
+            instructions: Vec::new(),
+            jump: None,
+            next: None,
+            indirect: false,
+            invalid: None,
+        }
+    }
+}
+
+/// An instruction that writes into the code of a reachable instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    /// Address of the writing instruction.
+    pub from: usize,
+    /// Address that is overwritten.
+    pub addr: usize,
+    /// Start of the instruction that is modified.
+    pub target: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    pub blocks: BTreeMap<usize, Block>,
+    pub patches: Vec<Patch>,
+}
+
+struct Decoded {
+    text: String,
+    len: usize,
+    inst: Instruction,
+}
+
+fn param(memory: &[i64], addr: usize, n: usize) -> i64 {
+    memory[addr + n]
+}
+
+/// Successors of a jump at `addr`: `(target, falls through, indirect)`.
+fn branch(memory: &[i64], addr: usize, inst: Instruction) -> (Option<usize>, bool, bool) {
+    let (on_true, cond, target) = match inst {
+        Instruction::JumpTrue(cond, target) => (true, cond, target),
+        Instruction::JumpFalse(cond, target) => (false, cond, target),
+        _ => return (None, true, false),
+    };
+    // an immediate condition makes the jump unconditional or a no-op
+    let taken = match cond {
+        1 => Some(taken(on_true, param(memory, addr, 1))),
+        _ => None,
+    };
+    if taken == Some(false) {
+        return (None, true, false);
+    }
+    let jump = match (target, param(memory, addr, 2)) {
+        (1, value) if value >= 0 => Some(value as usize),
+        _ => None,
+    };
+    (jump, taken.is_none(), jump.is_none())
+}
+
+/// Address written by the instruction at `addr` if it is known statically.
+fn written(memory: &[i64], addr: usize, inst: Instruction) -> Option<usize> {
+    let (mode, n) = match inst {
+        Instruction::Add(_, _, m)
+        | Instruction::Mul(_, _, m)
+        | Instruction::LessThan(_, _, m)
+        | Instruction::Equals(_, _, m) => (m, 3),
+        Instruction::In(m) => (m, 1),
+        _ => return None,
+    };
+    match (mode, param(memory, addr, n)) {
+        (0, value) if value >= 0 => Some(value as usize),
+        _ => None,
+    }
+}
+
+fn decoded(memory: &[i64], addr: usize) -> Option<Decoded> {
+    let (text, len) = decode(memory, addr)?;
+    let inst = instruction(memory[addr])?;
+    Some(Decoded { text, len, inst })
+}
+
+/// Decodes every instruction reachable from address 0 into basic blocks.
+///
+/// Jumps with a target in position or relative mode cannot be followed and
+/// mark their block `indirect`. Writes in position mode into the words of a
+/// reachable instruction are reported as patches.
+pub fn analyze(memory: &[i64]) -> Graph {
+    let mut code: BTreeMap<usize, Decoded> = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut pending = vec![0];
+    leaders.insert(0);
+    while let Some(mut addr) = pending.pop() {
+        while !code.contains_key(&addr) {
+            let d = match decoded(memory, addr) {
+                Some(d) => d,
+                None => break,
+            };
+            let next = addr + d.len;
+            let (jump, through, indirect) = branch(memory, addr, d.inst);
+            let halt = matches!(d.inst, Instruction::Halt);
+            let ends = jump.is_some() || indirect || !through;
+            code.insert(addr, d);
+            if let Some(target) = jump {
+                if leaders.insert(target) {
+                    pending.push(target);
+                }
+            }
+            if halt || !through {
+                break;
+            }
+            if ends {
+                leaders.insert(next);
+            }
+            addr = next;
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for start in leaders.iter() {
+        let mut block = Block::new(*start);
+        let mut addr = *start;
+        loop {
+            let d = match code.get(&addr) {
+                Some(d) => d,
+                None => {
+                    block.invalid = Some(addr);
+                    break;
+                }
+            };
+            block.instructions.push((addr, d.text.clone()));
+            let (jump, through, indirect) = branch(memory, addr, d.inst);
+            let next = addr + d.len;
+            if matches!(d.inst, Instruction::Halt) {
+                break;
+            }
+            if jump.is_some() || indirect || !through || leaders.contains(&next) {
+                block.jump = jump;
+                block.indirect = indirect;
+                block.next = if through { Some(next) } else { None };
+                break;
+            }
+            addr = next;
+        }
+        blocks.insert(*start, block);
+    }
+
+    let mut patches = Vec::new();
+    for (from, d) in code.iter() {
+        if let Some(addr) = written(memory, *from, d.inst) {
+            let owner = code.range(..=addr).next_back();
+            if let Some((target, owner)) = owner {
+                if addr < target + owner.len {
+                    patches.push(Patch {
+                        from: *from,
+                        addr,
+                        target: *target,
+                    });
+                }
+            }
+        }
+    }
+    Graph { blocks, patches }
+}
+
+impl Graph {
+    /// Block that contains the instruction at `addr`.
+    fn block_of(&self, addr: usize) -> Option<usize> {
+        self.blocks
+            .range(..=addr)
+            .next_back()
+            .filter(|(_, b)| b.instructions.iter().any(|(a, _)| *a == addr))
+            .map(|(start, _)| *start)
+    }
+
+    /// Renders the graph in Graphviz DOT. Jump edges are labelled, indirect
+    /// jumps lead to a `?` node and patches are drawn as dotted edges.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec![
+            "digraph intcode {".to_string(),
+            "    node [shape=box, fontname=monospace];".to_string(),
+        ];
+        let mut indirect = false;
+        for block in self.blocks.values() {
+            let mut label: String = block
+                .instructions
+                .iter()
+                .map(|(addr, text)| format!("{:04}: {}\\l", addr, text))
+                .collect();
+            if let Some(addr) = block.invalid {
+                label.push_str(&format!("{:04}: ???\\l", addr));
+            }
+            lines.push(format!("    b{} [label=\"{}\"];", block.start, label));
+            if let Some(target) = block.jump {
+                lines.push(format!("    b{} -> b{} [label=jump];", block.start, target));
+            }
+            if let Some(next) = block.next {
+                lines.push(format!("    b{} -> b{};", block.start, next));
+            }
+            if block.indirect {
+                indirect = true;
+                lines.push(format!("    b{} -> indirect [style=dashed];", block.start));
+            }
+        }
+        if indirect {
+            lines.push("    indirect [shape=diamond, label=\"?\"];".to_string());
+        }
+        for patch in self.patches.iter() {
+            if let (Some(from), Some(to)) = (self.block_of(patch.from), self.block_of(patch.target))
+            {
+                lines.push(format!(
+                    "    b{} -> b{} [style=dotted, label=\"write {}\"];",
+                    from, to, patch.addr
+                ));
+            }
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::create_memory;
+
+    #[test]
+    fn test_blocks() {
+        let source = "
+                    IN [x]
+            loop:   JF [x], #done
+                    OUT [x]
+                    ADD [x], #-1, [x]
+                    JT #1, #loop
+            done:   HLT
+            x:      DATA 0
+        ";
+        let graph = analyze(&assemble(source).unwrap());
+        let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0, 2, 5, 14]);
+        assert_eq!(graph.blocks[&0].next, Some(2));
+        assert_eq!(graph.blocks[&2].jump, Some(14));
+        assert_eq!(graph.blocks[&2].next, Some(5));
+        assert_eq!(graph.blocks[&5].instructions.len(), 3);
+        assert_eq!(graph.blocks[&5].jump, Some(2));
+        assert_eq!(graph.blocks[&5].next, None);
+        assert_eq!(
+            graph.blocks[&14].instructions,
+            vec![(14, "HLT".to_string())]
+        );
+        assert!(graph.patches.is_empty());
+    }
+
+    #[test]
+    fn test_immediate_conditions() {
+        // JT only jumps on positive conditions, like the machine
+        for data in ["1105,-1,4,99,104,7,99", "1106,-1,4,99,104,7,99"].iter() {
+            let graph = analyze(&create_memory(data).unwrap());
+            let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+            assert_eq!(starts, vec![0], "{}", data);
+            assert_eq!(graph.blocks[&0].instructions.len(), 2);
+            assert_eq!(graph.blocks[&0].jump, None);
+        }
+
+        let graph = analyze(&create_memory("1105,2,4,99,104,7,99").unwrap());
+        assert_eq!(graph.blocks[&0].jump, Some(4));
+        assert_eq!(graph.blocks[&0].next, None);
+    }
+
+    #[test]
+    fn test_indirect_and_patches() {
+        // reads the jump target of the next instruction
+        let memory = create_memory("3,4,1105,1,0,99").unwrap();
+        let graph = analyze(&memory);
+        assert_eq!(
+            graph.patches,
+            vec![Patch {
+                from: 0,
+                addr: 4,
+                target: 2
+            }]
+        );
+        let memory = create_memory("6,0,6,99,99,99,99").unwrap();
+        let graph = analyze(&memory);
+        assert!(graph.blocks[&0].indirect);
+        assert_eq!(graph.blocks[&0].next, Some(3));
+    }
+
+    #[test]
+    fn test_dot() {
+        let memory = create_memory("3,7,1005,7,6,99,4,7").unwrap();
+        let expected = "digraph intcode {\n    \
+                        node [shape=box, fontname=monospace];\n    \
+                        b0 [label=\"0000: IN [7]\\l0002: JT [7], #6\\l\"];\n    \
+                        b0 -> b6 [label=jump];\n    \
+                        b0 -> b5;\n    \
+                        b5 [label=\"0005: HLT\\l\"];\n    \
+                        b6 [label=\"0006: OUT [7]\\l0008: ???\\l\"];\n    \
+                        b0 -> b6 [style=dotted, label=\"write 7\"];\n\
+                        }";
+        assert_eq!(analyze(&memory).to_dot(), expected);
+    }
+}
@@ -1,10 +1,11 @@
 #[macro_use]
 extern crate log;
 
-use std::iter::repeat;
-use std::mem::replace;
+use std::collections::VecDeque;
+use std::io::Write;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
+use std::time::{Duration, Instant};
 
 #[macro_export]
 macro_rules! parse {
@@ -13,521 +14,930 @@ macro_rules! parse {
     };
 }
 
-type Memory = Vec<i64>;
-fn create_memory(data: String) -> Memory {
-    data.trim_end()
-        .split(",")
-        .map(|x| {
+pub mod ascii;
+pub mod asm;
+#[cfg(feature = "bigint")]
+pub mod big;
+pub mod debugger;
+pub mod disasm;
+mod error;
+pub mod flow;
+pub mod memory;
+pub mod network;
+pub mod pipeline;
+mod profile;
+pub mod registry;
+pub mod session;
+mod snapshot;
+pub mod text;
+mod trace;
+
+pub use error::IntcodeError;
+pub use memory::Ram;
+pub use profile::{Loop, Profile};
+pub use registry::InstructionSet;
+use session::Event;
+pub use snapshot::Snapshot;
+use trace::Record;
+
+pub type Memory = Vec<i64>;
+pub fn create_memory(data: &str) -> Result<Memory, IntcodeError> {
+    data.trim()
+        .split(',')
+        .enumerate()
+        .map(|(index, x)| {
             //debug!("cm_map{:?}", x);
-            x.parse().unwrap()
+            x.trim().parse().map_err(|_| IntcodeError::Parse {
+                index,
+                token: x.to_string(),
+            })
         })
         .collect()
 }
 
-struct IntCode {
-    memory: Memory,
-    tx: Sender<String>,
-    rx: Receiver<String>,
+/// Why `Machine::run_until_io` handed control back to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// An `In` instruction is waiting, push a value with `Machine::input`.
+    Input,
+    /// An `Out` instruction produced a value.
+    Output(i64),
+    /// The program executed opcode 99.
+    Halted,
+}
+
+/// A resource limit that stopped a machine, see `Machine::limit_instructions`
+/// and `Machine::limit_time`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Instructions(u64),
+    Time(Duration),
+}
+
+/// Why a machine stopped for good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The program executed opcode 99.
+    Halted,
+    /// The machine ran into a limit before halting.
+    Limit(Limit),
+}
+
+/// How `Add`, `Mul`, `ARB` and relative mode addresses treat results that do
+/// not fit into an `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// Results wrap around, the default.
+    Wrapping,
+    /// Overflow stops the machine with `IntcodeError::Overflow`.
+    Checked,
+}
+
+/// Instructions between two looks at the clock for `Limit::Time`.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Final report of a machine that stopped, see `Machine::exit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exit {
+    pub reason: Reason,
+    /// Number of instructions executed, an `In` waiting for input is not counted.
+    pub instructions: u64,
+    pub ptr: usize,
+    pub rel: i64,
+    pub memory: Ram,
+    /// Counts collected since `Machine::profile`.
+    pub profile: Option<Profile>,
+}
+
+/// A single intcode program that is driven by its caller, no threads involved.
+pub struct Machine {
+    memory: Ram,
+    input: VecDeque<i64>,
+    state: Option<State>,
+    stop: Option<Reason>,
+    instructions: u64,
     ptr: usize,
     rel: i64,
+    tracer: Option<Box<dyn Write + Send>>,
+    record: Option<Record>,
+    max_instructions: Option<u64>,
+    max_time: Option<Duration>,
+    started: Option<Instant>,
+    profile: Option<Profile>,
+    custom: Option<InstructionSet>,
+    arithmetic: Arithmetic,
+    session: Option<Box<dyn Write + Send>>,
 }
 
-impl IntCode {
-    fn new(line: String, tx: Sender<String>, rx: Receiver<String>) -> Self {
-        let memory = create_memory(line).to_owned();
+impl Machine {
+    pub fn new(memory: Memory) -> Self {
         Self {
-            memory: memory,
-            tx,
-            rx,
+            memory: Ram::new(memory, memory::DEFAULT_SIZE),
+            input: VecDeque::new(),
+            state: None,
+            stop: None,
+            instructions: 0,
             ptr: 0,
             rel: 0,
+            tracer: None,
+            record: None,
+            max_instructions: None,
+            max_time: None,
+            started: None,
+            profile: None,
+            custom: None,
+            arithmetic: Arithmetic::Wrapping,
+            session: None,
+        }
+    }
+    pub fn arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+    /// Limits the machine to addresses below `size`, touching memory above
+    /// fails with `IntcodeError::OutOfMemory`.
+    pub fn limit_memory(&mut self, size: usize) {
+        self.memory.resize(size);
+    }
+    /// Stops the machine with `IntcodeError::LimitExceeded` once it executed
+    /// `count` instructions in total.
+    pub fn limit_instructions(&mut self, count: u64) {
+        self.max_instructions = Some(count);
+        self.resume();
+    }
+    /// Stops the machine with `IntcodeError::LimitExceeded` once it spent
+    /// `duration`, counted from the first step after the limit was set. Time
+    /// blocked on input in `spawn` counts as well, but the clock is only
+    /// checked while executing.
+    pub fn limit_time(&mut self, duration: Duration) {
+        self.max_time = Some(duration);
+        self.started = None;
+        self.resume();
+    }
+    fn resume(&mut self) {
+        if let Some(Reason::Limit(_)) = self.stop {
+            self.stop = None;
+        }
+    }
+    fn check_limits(&mut self) -> Option<Limit> {
+        if let Some(count) = self.max_instructions {
+            if self.instructions >= count {
+                return Some(Limit::Instructions(count));
+            }
         }
+        if let Some(duration) = self.max_time {
+            let started = *self.started.get_or_insert_with(Instant::now);
+            if self.instructions.is_multiple_of(CLOCK_INTERVAL) && started.elapsed() >= duration {
+                return Some(Limit::Time(duration));
+            }
+        }
+        None
     }
-    fn extend(&mut self, addr: &usize) {
-        // debug!("extend({}, {})", addr, self.memory.len());
-        if self.memory.len() <= *addr {
-            self.memory
-                .extend(repeat(0).take(*addr - self.memory.len() + 1))
+    fn code(&self) -> i64 {
+        self.memory.get(self.ptr)
+    }
+    fn negative(&self, operand: usize, addr: i64) -> IntcodeError {
+        IntcodeError::NegativeAddress {
+            ptr: self.ptr,
+            code: self.code(),
+            operand,
+            addr,
         }
     }
-    fn get_address(&mut self, mode: u32, offset: usize) -> usize {
+    fn get_address(&mut self, mode: u32, offset: usize) -> Result<usize, IntcodeError> {
         let addr = self.ptr + offset;
+        let param = self.memory.get(addr);
         let out = match mode {
-            0 => self.memory[addr] as usize,
-            1 => addr,
-            2 => {
-                let addr = self.rel + self.memory[addr];
-                addr as usize
+            0 => param,
+            1 => addr as i64,
+            2 => compute(self, self.rel, param, i64::checked_add, i64::wrapping_add)?,
+            _ => {
+                return Err(IntcodeError::UnknownMode {
+                    ptr: self.ptr,
+                    code: self.code(),
+                    operand: offset,
+                    mode,
+                })
             }
-            _ => unimplemented!(),
         };
-        self.extend(&out);
-        debug!("get_addr({}, {}) -> {}", mode, offset, out);
-        out
-    }
-
-    fn input(&self) -> i64 {
-        let line = self.rx.recv().expect("input: cannot receive");
-        parse!(line, i64)
+        if out < 0 {
+            return Err(self.negative(offset, out));
+        }
+        let out = out as usize;
+        if out >= self.memory.size() {
+            return Err(IntcodeError::OutOfMemory {
+                ptr: self.ptr,
+                code: self.code(),
+                operand: offset,
+                addr: out,
+                size: self.memory.size(),
+            });
+        }
+        if let Some(record) = self.record.as_mut() {
+            record.modes.push(mode);
+            record.addrs.push(out);
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            profile.touch(out);
+        }
+        Ok(out)
     }
-    fn output(&mut self, value: i64) {
-        self.tx
-            .send(format!("{}", value))
-            .expect("output: cannot transmit value");
+    fn read(&mut self, addr: usize) -> i64 {
+        let value = self.memory.get(addr);
+        if let Some(record) = self.record.as_mut() {
+            record.reads.push(value);
+        }
+        value
     }
-
-    pub fn run(&mut self) {
-        let mut last = 0;
-        loop {
-            let code = self.memory[self.ptr];
-            debug!("run|ptr:{}, rel:{}, {:?}", self.ptr, self.rel, self.memory);
-            let inst = instruction(code);
-            self.ptr = inst.call(self);
-            if self.ptr == last {
-                break;
-            }
-            last = self.ptr;
+    fn write(&mut self, addr: usize, value: i64) {
+        self.memory.set(addr, value);
+        if let Some(record) = self.record.as_mut() {
+            record.write = Some((addr, value));
         }
     }
-}
-
-trait Instruction {
-    fn call(&self, intcode: &mut IntCode) -> usize;
-}
-
-macro_rules! pop_digit {
-    ($x:expr) => {
-        $x.pop().unwrap_or('0').to_digit(10).unwrap_or(0)
-    };
-}
 
-#[derive(Debug)]
-struct Halt();
-impl Halt {
-    fn new() -> Self {
-        Self()
+    /// Writes one JSON line per executed instruction to `out`: pointer, opcode,
+    /// parameter modes, resolved addresses, values read, value written and
+    /// relative base. Tracing stops if `out` fails.
+    pub fn trace<W: Write + Send + 'static>(&mut self, out: W) {
+        self.tracer = Some(Box::new(out));
     }
-}
 
-impl Instruction for Halt {
-    fn call(&self, intcode: &mut IntCode) -> usize {
-        debug!("{:?}", self);
-        intcode.ptr + 0
+    fn emit(&mut self) {
+        let record = match self.record.take() {
+            Some(record) => record,
+            None => return,
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            if let Err(err) = writeln!(tracer, "{}", record.to_json()) {
+                warn!("trace: cannot write record, tracing stopped: {}", err);
+                self.tracer = None;
+            }
+        }
     }
-}
-#[derive(Debug)]
-struct Add(u32, u32, u32);
 
-impl Add {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        let b = pop_digit!(&mut params);
-        let c = pop_digit!(&mut params);
-        Self(a, b, c)
-    }
-}
-impl Instruction for Add {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        let b = p.get_address(self.1, 2);
-        let c = p.get_address(self.2, 3);
-        debug!("{:?}:{} {} {}", self, a, b, c);
-        let value = p.memory[a] + p.memory[b];
-        replace(&mut p.memory[c], value);
-        p.ptr + 4
+    /// Queues a value for the next `In` instruction.
+    pub fn input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    /// Overwrites memory from `addr` on with `values`, e.g. noun and verb
+    /// before a run. Patching does not count as a write for the tracer.
+    /// Nothing is written if a word would land at or above the memory size,
+    /// that fails with `IntcodeError::OutOfMemory` for operand 0.
+    pub fn patch(&mut self, addr: usize, values: &[i64]) -> Result<(), IntcodeError> {
+        if !values.is_empty() {
+            match addr.checked_add(values.len() - 1) {
+                Some(last) if last < self.memory.size() => {}
+                last => {
+                    return Err(IntcodeError::OutOfMemory {
+                        ptr: self.ptr,
+                        code: self.code(),
+                        operand: 0,
+                        addr: last.unwrap_or(usize::MAX),
+                        size: self.memory.size(),
+                    })
+                }
+            }
+        }
+        for (offset, value) in values.iter().enumerate() {
+            self.memory.set(addr + offset, *value);
+        }
+        Ok(())
     }
-}
 
-#[derive(Debug)]
-struct Mul(u32, u32, u32);
-impl Mul {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        let b = pop_digit!(&mut params);
-        let c = pop_digit!(&mut params);
-        Self(a, b, c)
+    /// Value at `addr`, unmapped words read as 0.
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.memory.get(addr)
     }
-}
-impl Instruction for Mul {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        let b = p.get_address(self.1, 2);
-        let c = p.get_address(self.2, 3);
-        debug!("{:?}:{} {} {}", self, a, b, c);
-        let value = p.memory[a] * p.memory[b];
-        replace(&mut p.memory[c], value);
-        p.ptr + 4
-    }
-}
-#[derive(Debug)]
-struct In(u32);
-impl In {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        Self(a)
-    }
-}
 
-impl Instruction for In {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        debug!("{:?}:{}", self, a);
-        let value = p.input();
-        replace(&mut p.memory[a], value);
-        p.ptr + 2
+    /// The whole memory, see `Ram::range` to read a block of words.
+    pub fn memory(&self) -> &Ram {
+        &self.memory
     }
-}
 
-#[derive(Debug)]
-struct Out(u32);
-
-impl Out {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        Self(a)
+    pub fn halted(&self) -> bool {
+        self.stop == Some(Reason::Halted)
     }
-}
 
-impl Instruction for Out {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        debug!("{:?}:{}", self, a);
-        p.output(p.memory[a]);
-        p.ptr + 2
+    /// Number of instructions executed so far.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
     }
-}
-#[derive(Debug)]
-struct JumpTrue(u32, u32);
-impl JumpTrue {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        let b = pop_digit!(&mut params);
-        Self(a, b)
+
+    /// Consumes a stopped machine into its exit report, `None` while it can still run.
+    pub fn exit(self) -> Option<Exit> {
+        let reason = self.stop?;
+        Some(Exit {
+            reason,
+            instructions: self.instructions,
+            ptr: self.ptr,
+            rel: self.rel,
+            memory: self.memory,
+            profile: self.profile,
+        })
     }
-}
 
-impl Instruction for JumpTrue {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        let b = p.get_address(self.1, 2);
-        let va = p.memory[a];
-        let vb = p.memory[b];
-        debug!("{:?}:[{}, {}] -> [{}, {}]", self, a, b, va, vb);
-        if va > 0 {
-            return vb as usize;
+    /// Executes a single instruction, returns the state if it stopped the machine.
+    pub fn step(&mut self) -> Result<Option<State>, IntcodeError> {
+        match self.stop {
+            Some(Reason::Halted) => return Ok(Some(State::Halted)),
+            Some(Reason::Limit(limit)) => {
+                return Err(IntcodeError::LimitExceeded {
+                    ptr: self.ptr,
+                    limit,
+                })
+            }
+            None => {}
+        }
+        if let Some(limit) = self.check_limits() {
+            self.stop = Some(Reason::Limit(limit));
+            return Err(IntcodeError::LimitExceeded {
+                ptr: self.ptr,
+                limit,
+            });
+        }
+        let code = self.code();
+        if self.tracer.is_some() {
+            self.record = Some(Record::new(self.ptr, code, self.rel));
         }
-        p.ptr + 3
+        let custom = self.custom.as_ref().and_then(|set| set.get(code));
+        let next = match custom {
+            Some(operation) => operation.execute(&mut registry::Context { machine: self }),
+            None => instruction(code)
+                .ok_or(IntcodeError::UnknownOpcode {
+                    ptr: self.ptr,
+                    code,
+                })?
+                .call(self),
+        };
+        if next.is_err() || self.state == Some(State::Input) {
+            self.record = None;
+        } else {
+            self.instructions += 1;
+            self.emit();
+            if let (Some(profile), Ok(next)) = (self.profile.as_mut(), next.as_ref()) {
+                profile.count(self.ptr, code, *next);
+            }
+            match self.state {
+                Some(State::Output(value)) => self.log(Event::Output(value)),
+                Some(State::Halted) => self.log(Event::Halt),
+                _ => {}
+            }
+        }
+        self.ptr = next?;
+        Ok(self.state.take())
     }
-}
 
-#[derive(Debug)]
-struct JumpFalse(u32, u32);
-impl JumpFalse {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        let b = pop_digit!(&mut params);
-        Self(a, b)
+    /// Runs until the program needs input, produces output or halts.
+    pub fn run_until_io(&mut self) -> Result<State, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
     }
 }
 
-impl Instruction for JumpFalse {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        let b = p.get_address(self.1, 2);
-        let va = p.memory[a];
-        let vb = p.memory[b];
-        debug!("{:?}:[{}, {}] -> [{}, {}]", self, a, b, va, vb);
-        if va == 0 {
-            return vb as usize;
-        }
-        p.ptr + 3
+fn compute(
+    p: &Machine,
+    a: i64,
+    b: i64,
+    checked: fn(i64, i64) -> Option<i64>,
+    wrapping: fn(i64, i64) -> i64,
+) -> Result<i64, IntcodeError> {
+    match p.arithmetic {
+        Arithmetic::Wrapping => Ok(wrapping(a, b)),
+        Arithmetic::Checked => checked(a, b).ok_or(IntcodeError::Overflow {
+            ptr: p.ptr,
+            code: p.code(),
+            a,
+            b,
+        }),
     }
 }
 
-#[derive(Debug)]
-struct LessThan(u32, u32, u32);
-impl LessThan {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        let b = pop_digit!(&mut params);
-        let c = pop_digit!(&mut params);
-        Self(a, b, c)
+/// Whether `JT` (`on_true`) or `JF` jumps on condition `cond`. `JT` only
+/// jumps on positive values, like the day5 machine.
+fn taken(on_true: bool, cond: i64) -> bool {
+    if on_true {
+        cond > 0
+    } else {
+        cond == 0
     }
 }
 
-impl Instruction for LessThan {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        let b = p.get_address(self.1, 2);
-        let c = p.get_address(self.2, 3);
-        debug!("{:?}:{} {} {}", self, a, b, c);
-        let value = p.memory[a] < p.memory[b];
-        if value {
-            replace(&mut p.memory[c], 1);
-        } else {
-            replace(&mut p.memory[c], 0);
-        }
-        p.ptr + 4
+fn jump(p: &Machine, target: i64) -> Result<usize, IntcodeError> {
+    if target < 0 {
+        return Err(p.negative(2, target));
     }
+    Ok(target as usize)
 }
 
-#[derive(Debug)]
-struct Equals(u32, u32, u32);
-impl Equals {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        let b = pop_digit!(&mut params);
-        let c = pop_digit!(&mut params);
-        Self(a, b, c)
-    }
+/// A decoded instruction, the fields are the parameter modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Add(u32, u32, u32),
+    Mul(u32, u32, u32),
+    In(u32),
+    Out(u32),
+    JumpTrue(u32, u32),
+    JumpFalse(u32, u32),
+    LessThan(u32, u32, u32),
+    Equals(u32, u32, u32),
+    AdjustRel(u32),
+    Halt,
 }
 
-impl Instruction for Equals {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        let b = p.get_address(self.1, 2);
-        let c = p.get_address(self.2, 3);
-        debug!("{:?}:{} {} {}", self, a, b, c);
-        let value = p.memory[a] == p.memory[b];
-        if value {
-            replace(&mut p.memory[c], 1);
-        } else {
-            replace(&mut p.memory[c], 0);
+impl Instruction {
+    fn call(self, p: &mut Machine) -> Result<usize, IntcodeError> {
+        debug!("{:04}: {:?}", p.ptr, self);
+        match self {
+            Instruction::Add(ma, mb, mc) => {
+                let a = p.get_address(ma, 1)?;
+                let b = p.get_address(mb, 2)?;
+                let c = p.get_address(mc, 3)?;
+                let (a, b) = (p.read(a), p.read(b));
+                let value = compute(p, a, b, i64::checked_add, i64::wrapping_add)?;
+                p.write(c, value);
+                Ok(p.ptr + 4)
+            }
+            Instruction::Mul(ma, mb, mc) => {
+                let a = p.get_address(ma, 1)?;
+                let b = p.get_address(mb, 2)?;
+                let c = p.get_address(mc, 3)?;
+                let (a, b) = (p.read(a), p.read(b));
+                let value = compute(p, a, b, i64::checked_mul, i64::wrapping_mul)?;
+                p.write(c, value);
+                Ok(p.ptr + 4)
+            }
+            Instruction::In(ma) => {
+                let a = p.get_address(ma, 1)?;
+                match p.next_input() {
+                    Some(value) => {
+                        p.write(a, value);
+                        Ok(p.ptr + 2)
+                    }
+                    None => {
+                        p.state = Some(State::Input);
+                        Ok(p.ptr)
+                    }
+                }
+            }
+            Instruction::Out(ma) => {
+                let a = p.get_address(ma, 1)?;
+                let value = p.read(a);
+                p.state = Some(State::Output(value));
+                Ok(p.ptr + 2)
+            }
+            Instruction::JumpTrue(ma, mb) => {
+                let a = p.get_address(ma, 1)?;
+                let b = p.get_address(mb, 2)?;
+                if taken(true, p.read(a)) {
+                    let target = p.read(b);
+                    return jump(p, target);
+                }
+                Ok(p.ptr + 3)
+            }
+            Instruction::JumpFalse(ma, mb) => {
+                let a = p.get_address(ma, 1)?;
+                let b = p.get_address(mb, 2)?;
+                if taken(false, p.read(a)) {
+                    let target = p.read(b);
+                    return jump(p, target);
+                }
+                Ok(p.ptr + 3)
+            }
+            Instruction::LessThan(ma, mb, mc) => {
+                let a = p.get_address(ma, 1)?;
+                let b = p.get_address(mb, 2)?;
+                let c = p.get_address(mc, 3)?;
+                let value = p.read(a) < p.read(b);
+                p.write(c, value as i64);
+                Ok(p.ptr + 4)
+            }
+            Instruction::Equals(ma, mb, mc) => {
+                let a = p.get_address(ma, 1)?;
+                let b = p.get_address(mb, 2)?;
+                let c = p.get_address(mc, 3)?;
+                let value = p.read(a) == p.read(b);
+                p.write(c, value as i64);
+                Ok(p.ptr + 4)
+            }
+            Instruction::AdjustRel(ma) => {
+                let a = p.get_address(ma, 1)?;
+                let a = p.read(a);
+                p.rel = compute(p, p.rel, a, i64::checked_add, i64::wrapping_add)?;
+                Ok(p.ptr + 2)
+            }
+            Instruction::Halt => {
+                p.stop = Some(Reason::Halted);
+                p.state = Some(State::Halted);
+                Ok(p.ptr)
+            }
         }
-        p.ptr + 4
     }
 }
-#[derive(Debug)]
-struct AdjustRel(u32);
-impl AdjustRel {
-    fn new(params: String) -> Self {
-        let mut params = params;
-        let a = pop_digit!(&mut params);
-        Self(a)
-    }
+
+/// Splits an instruction word into its opcode and the parameter mode digits,
+/// 1002 becomes `(2, 10)`.
+fn opcode(code: i64) -> (u8, i64) {
+    ((code % 100) as u8, code / 100)
 }
 
-impl Instruction for AdjustRel {
-    fn call(&self, p: &mut IntCode) -> usize {
-        let a = p.get_address(self.0, 1);
-        debug!("{:?}:{}", self, a);
-        let value = p.memory[a];
-        p.rel += value;
-        p.ptr + 2
-    }
+/// Mode digit of the 1-based parameter `n`.
+fn mode(modes: i64, n: u32) -> u32 {
+    (modes / 10i64.pow(n - 1) % 10) as u32
 }
 
-fn opcode(code: i64) -> (u8, String) {
-    let digits = code.to_string();
-    if digits.len() <= 2 {
-        return (code as u8, String::from(""));
-    }
-    (
-        digits[digits.len() - 2..]
-            .parse::<u8>()
-            .expect("coudnt parse opcode"),
-        digits[..digits.len() - 2].to_string(),
-    )
+fn instruction(code: i64) -> Option<Instruction> {
+    if code < 0 {
+        return None;
+    }
+    let (op, modes) = opcode(code);
+    let m = |n| mode(modes, n);
+    let inst = match op {
+        99 => Instruction::Halt,
+        1 => Instruction::Add(m(1), m(2), m(3)),
+        2 => Instruction::Mul(m(1), m(2), m(3)),
+        3 => Instruction::In(m(1)),
+        4 => Instruction::Out(m(1)),
+        5 => Instruction::JumpTrue(m(1), m(2)),
+        6 => Instruction::JumpFalse(m(1), m(2)),
+        7 => Instruction::LessThan(m(1), m(2), m(3)),
+        8 => Instruction::Equals(m(1), m(2), m(3)),
+        9 => Instruction::AdjustRel(m(1)),
+        _ => return None,
+    };
+    Some(inst)
 }
 
-fn instruction(code: i64) -> Box<dyn Instruction> {
-    let (op, params) = opcode(code);
-    debug!("inst({},{})", op, params);
-    match op {
-        99 => Box::new(Halt::new()),
-        1 => Box::new(Add::new(params)),
-        2 => Box::new(Mul::new(params)),
-        3 => Box::new(In::new(params)),
-        4 => Box::new(Out::new(params)),
-        5 => Box::new(JumpTrue::new(params)),
-        6 => Box::new(JumpFalse::new(params)),
-        7 => Box::new(LessThan::new(params)),
-        8 => Box::new(Equals::new(params)),
-        9 => Box::new(AdjustRel::new(params)),
-        _ => unimplemented!(),
+fn drive(mut machine: Machine, tx: Sender<i64>, rx: Receiver<i64>) -> Result<Exit, IntcodeError> {
+    loop {
+        match machine.run_until_io()? {
+            State::Input => match rx.recv() {
+                Ok(value) => machine.input(value),
+                Err(_) => {
+                    return Err(IntcodeError::InputClosed {
+                        ptr: machine.ptr,
+                        code: machine.code(),
+                    })
+                }
+            },
+            State::Output(value) => {
+                if tx.send(value).is_err() {
+                    return Err(IntcodeError::OutputClosed {
+                        ptr: machine.ptr,
+                        code: machine.code(),
+                        value,
+                    });
+                }
+            }
+            State::Halted => return Ok(machine.exit().expect("halted machine has an exit")),
+        }
     }
 }
 
-pub fn spawn(
-    data: String,
-    init: Option<String>,
-) -> (Sender<String>, Receiver<String>, thread::JoinHandle<()>) {
+type Spawned = (
+    Sender<i64>,
+    Receiver<i64>,
+    thread::JoinHandle<Result<Exit, IntcodeError>>,
+);
+
+fn start<F>(init: Option<i64>, machine: F) -> Spawned
+where
+    F: FnOnce() -> Result<Machine, IntcodeError> + Send + 'static,
+{
     let (tx, rxp) = channel();
     let (txp, rx) = channel();
-    let handle = thread::spawn(move || IntCode::new(data, txp, rxp).run());
-    match init {
-        Some(data) => tx.send(data).unwrap(),
-        None => {}
-    };
+    // `init` is queued in the worker, a program that fails to load or halts
+    // right away must not make the caller's send fail
+    let handle = thread::spawn(move || {
+        let mut machine = machine()?;
+        if let Some(value) = init {
+            machine.input(value);
+        }
+        drive(machine, txp, rxp)
+    });
     (tx, rx, handle)
 }
 
+pub fn spawn(data: String, init: Option<i64>) -> Spawned {
+    start(init, move || Ok(Machine::new(create_memory(&data)?)))
+}
+
+/// Like `spawn` for a machine that was set up beforehand, e.g. with limits.
+pub fn spawn_machine(machine: Machine, init: Option<i64>) -> Spawned {
+    start(init, move || Ok(machine))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     #[test]
     fn test_opcode() {
-        let (op, param) = opcode(1002);
+        let (op, modes) = opcode(1002);
         assert_eq!(op, 2);
+        assert_eq!(modes, 10);
 
-        let add = Add::new(param);
-        assert_eq!(add.0, 0);
-        assert_eq!(add.1, 1);
-        assert_eq!(add.2, 0);
+        assert_eq!(instruction(1002), Some(Instruction::Mul(0, 1, 0)));
+        assert_eq!(instruction(21101), Some(Instruction::Add(1, 1, 2)));
+        assert_eq!(instruction(204), Some(Instruction::Out(2)));
+        assert_eq!(instruction(99), Some(Instruction::Halt));
+        assert_eq!(instruction(42), None);
+        assert_eq!(instruction(-101), None);
     }
     #[test]
-    fn test_add() {
-        let data = String::from("1,0,0,0,99");
-        let (tx, rx) = channel();
-        let mut p = IntCode::new(data, tx, rx);
-        p.run();
-        let expected: Memory = vec![2, 0, 0, 0, 99];
-        assert_eq!(p.memory, expected);
+    fn test_run_until_io() {
+        let data = String::from("3,9,8,9,10,9,4,9,99,-1,8");
+        let mut p = Machine::new(create_memory(&data).unwrap());
+        assert_eq!(p.run_until_io(), Ok(State::Input));
+        assert_eq!(p.run_until_io(), Ok(State::Input));
+        p.input(8);
+        assert_eq!(p.run_until_io(), Ok(State::Output(1)));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
     }
     #[test]
-    fn test_mul() {
-        let data = String::from("2,3,0,3,99");
-        let (tx, rx) = channel();
-        let mut p = IntCode::new(data, tx, rx);
-        p.run();
-        let expected: Memory = vec![2, 3, 0, 6, 99];
-        assert_eq!(p.memory, expected);
-
-        let data = String::from("2,4,4,5,99,0");
-        let (tx, rx) = channel();
-        let mut p = IntCode::new(data, tx, rx);
-        p.run();
-        let expected: Memory = vec![2, 4, 4, 5, 99, 9801];
-        assert_eq!(p.memory, expected);
-
-        let data = String::from("1,1,1,4,99,5,6,0,99");
-        let (tx, rx) = channel();
-        let mut p = IntCode::new(data, tx, rx);
-        p.run();
-        let expected: Memory = vec![30, 1, 1, 4, 2, 5, 6, 0, 99];
-        assert_eq!(p.memory, expected);
+    fn test_jump_to_self() {
+        let mut p = Machine::new(create_memory("1106,0,0,99").unwrap());
+        for _ in 0..3 {
+            assert_eq!(p.step(), Ok(None));
+        }
+        assert!(!p.halted());
+        assert_eq!(p.instructions(), 3);
+        assert!(p.exit().is_none());
     }
     #[test]
-    fn test_pos_eq8() {
-        let data = String::from("3,9,8,9,10,9,4,9,99,-1,8");
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("1".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 0);
-        }
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("8".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 1);
-        }
+    fn test_exit() {
+        let (tx, rx, handle) = spawn(String::from("3,11,109,7,1001,11,1,11,204,4,99,0"), None);
+        tx.send(41).unwrap();
+        assert_eq!(rx.recv().unwrap(), 42);
+        let exit = handle.join().unwrap().unwrap();
+        assert_eq!(exit.reason, Reason::Halted);
+        assert_eq!(exit.instructions, 5);
+        assert_eq!(exit.ptr, 10);
+        assert_eq!(exit.rel, 7);
+        assert_eq!(exit.memory[11], 42);
     }
-
     #[test]
-    fn test_pos_lt8() {
-        let data = String::from("3,9,7,9,10,9,4,9,99,-1,8");
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("1".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 1);
+    fn test_patch() {
+        let mut p = Machine::new(create_memory("1,0,0,3,2,3,11,0,99,30,40,50").unwrap());
+        assert_eq!(p.patch(1, &[9, 10]), Ok(()));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        assert_eq!(p.peek(0), 3500);
+        assert_eq!(p.memory().range(0, 4), vec![3500, 9, 10, 70]);
+
+        let mut p = Machine::new(create_memory("4,20,99").unwrap());
+        p.patch(20, &[7]).unwrap();
+        let (_tx, rx, handle) = spawn_machine(p, None);
+        assert_eq!(rx.recv().unwrap(), 7);
+        assert_eq!(
+            handle.join().unwrap().unwrap().memory.range(19, 21),
+            vec![0, 7]
+        );
+
+        let mut p = Machine::new(create_memory("99").unwrap());
+        p.limit_memory(16);
+        assert_eq!(
+            p.patch(15, &[1, 2]),
+            Err(IntcodeError::OutOfMemory {
+                ptr: 0,
+                code: 99,
+                operand: 0,
+                addr: 16,
+                size: 16
+            })
+        );
+        assert_eq!(p.peek(15), 0);
+        assert!(matches!(
+            p.patch(usize::MAX, &[1, 2]),
+            Err(IntcodeError::OutOfMemory {
+                addr: usize::MAX,
+                ..
+            })
+        ));
+        assert_eq!(p.patch(15, &[1]), Ok(()));
+        assert_eq!(p.patch(usize::MAX, &[]), Ok(()));
+    }
+    #[derive(Clone, Default)]
+    struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
         }
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("8".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 0);
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
         }
     }
-
     #[test]
-    fn test_immediate_eq8() {
-        let data = String::from("3,3,1108,-1,8,3,4,3,99");
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("1".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 0);
-        }
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("8".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 1);
-        }
+    fn test_trace() {
+        let out = Shared::default();
+        let mut p = Machine::new(create_memory("3,0,1002,0,3,0,4,0,99").unwrap());
+        p.trace(out.clone());
+        assert_eq!(p.run_until_io(), Ok(State::Input));
+        p.input(7);
+        assert_eq!(p.run_until_io(), Ok(State::Output(21)));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        let trace = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"ptr":0,"op":3,"modes":[0],"addrs":[0],"reads":[],"write":{"addr":0,"value":7},"rel":0}"#,
+                r#"{"ptr":2,"op":2,"modes":[0,1,0],"addrs":[0,4,0],"reads":[7,3],"write":{"addr":0,"value":21},"rel":0}"#,
+                r#"{"ptr":6,"op":4,"modes":[0],"addrs":[0],"reads":[21],"write":null,"rel":0}"#,
+                r#"{"ptr":8,"op":99,"modes":[],"addrs":[],"reads":[],"write":null,"rel":0}"#,
+            ]
+        );
     }
-
     #[test]
-    fn test_immediate_lt8() {
-        let data = String::from("3,3,1107,-1,8,3,4,3,99");
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("1".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 1);
-        }
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("8".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 0);
-        }
+    fn test_sparse_memory() {
+        let data = "21101,6,7,1000000000000,4,1000000000000,204,999999999999,99";
+        let mut p = Machine::new(create_memory(data).unwrap());
+        assert_eq!(p.run_until_io(), Ok(State::Output(13)));
+        assert_eq!(p.run_until_io(), Ok(State::Output(0)));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+
+        let mut p = Machine::new(create_memory(data).unwrap());
+        p.limit_memory(1 << 20);
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::OutOfMemory {
+                ptr: 0,
+                code: 21101,
+                operand: 3,
+                addr: 1000000000000,
+                size: 1 << 20
+            })
+        );
     }
     #[test]
-    fn test_pos_jump() {
-        let data = String::from("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9");
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("8".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 1);
-        }
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("0".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 0);
-        }
+    fn test_instruction_limit() {
+        let mut p = Machine::new(create_memory("1106,0,0,99").unwrap());
+        p.limit_instructions(100);
+        let limit = Limit::Instructions(100);
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::LimitExceeded { ptr: 0, limit })
+        );
+        assert_eq!(p.step(), Err(IntcodeError::LimitExceeded { ptr: 0, limit }));
+        assert_eq!(p.instructions(), 100);
+        p.limit_instructions(101);
+        assert_eq!(p.step(), Ok(None));
+        assert!(p.step().is_err());
+        let exit = p.exit().unwrap();
+        assert_eq!(exit.reason, Reason::Limit(Limit::Instructions(101)));
     }
-
     #[test]
-    fn test_immediate_jump() {
-        let data = String::from("3,3,1105,-1,9,1101,0,0,12,4,12,99,1");
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("8".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 1);
-        }
-        {
-            let (_tx, rx, _) = spawn(data.clone(), Some("0".to_string()));
-            let pout = rx.recv().expect("cannot recv");
-            assert_eq!(parse!(pout, i64), 0);
-        }
+    fn test_time_limit() {
+        let mut p = Machine::new(create_memory("1105,1,0,99").unwrap());
+        p.limit_time(Duration::from_millis(20));
+        let (_tx, _rx, handle) = spawn_machine(p, None);
+        assert_eq!(
+            handle.join().unwrap(),
+            Err(IntcodeError::LimitExceeded {
+                ptr: 0,
+                limit: Limit::Time(Duration::from_millis(20))
+            })
+        );
+
+        // a new limit starts a new clock
+        let mut p = Machine::new(create_memory("1105,1,0,99").unwrap());
+        p.limit_time(Duration::from_millis(20));
+        assert!(p.run_until_io().is_err());
+        thread::sleep(Duration::from_millis(60));
+        p.limit_time(Duration::from_millis(40));
+        let started = Instant::now();
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::LimitExceeded {
+                ptr: 0,
+                limit: Limit::Time(Duration::from_millis(40))
+            })
+        );
+        assert!(started.elapsed() >= Duration::from_millis(40));
     }
     #[test]
-    fn test_relative_mode() {
-        env_logger::init();
-        let data = String::from("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
-        let (_tx, rx, _) = spawn(data.clone(), None);
-        let mut output: Vec<String> = Vec::with_capacity(16);
-        for recv in rx {
-            output.push(recv);
-        }
-        assert_eq!(output.join(","), data);
+    fn test_errors() {
+        let err = create_memory("1,0,x,0,99").unwrap_err();
+        assert_eq!(
+            err,
+            IntcodeError::Parse {
+                index: 2,
+                token: "x".to_string()
+            }
+        );
+
+        let mut p = Machine::new(create_memory("1101,1,1,5,42,0").unwrap());
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::UnknownOpcode { ptr: 4, code: 42 })
+        );
+
+        let mut p = Machine::new(create_memory("3101,1,1,5,99").unwrap());
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::UnknownMode {
+                ptr: 0,
+                code: 3101,
+                operand: 2,
+                mode: 3
+            })
+        );
+
+        let mut p = Machine::new(create_memory("109,-5,2201,0,0,0,99").unwrap());
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::NegativeAddress {
+                ptr: 2,
+                code: 2201,
+                operand: 1,
+                addr: -5
+            })
+        );
+
+        let mut p = Machine::new(create_memory("1105,1,-1").unwrap());
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::NegativeAddress {
+                ptr: 0,
+                code: 1105,
+                operand: 2,
+                addr: -1
+            })
+        );
+    }
+    #[test]
+    fn test_input_closed() {
+        let (tx, _rx, handle) = spawn(String::from("3,0,99"), None);
+        drop(tx);
+        assert_eq!(
+            handle.join().unwrap(),
+            Err(IntcodeError::InputClosed { ptr: 0, code: 3 })
+        );
     }
 
     #[test]
-    fn test_sixteen_digits() {
-        let data = String::from("1102,34915192,34915192,7,4,7,99,0");
-        let (_tx, rx, _) = spawn(data.clone(), None);
-        let output = rx.recv().unwrap();
-        assert_eq!(output, "1219070632396864".to_string());
+    fn test_spawn_init() {
+        // the init value must not be sent to a worker that already ended
+        for _ in 0..200 {
+            let (_tx, _rx, handle) = spawn(String::from("1,x,99"), Some(1));
+            assert_eq!(
+                handle.join().unwrap().unwrap_err(),
+                IntcodeError::Parse {
+                    index: 1,
+                    token: "x".to_string()
+                }
+            );
+            let (_tx, _rx, handle) = spawn(String::from("99"), Some(1));
+            assert_eq!(handle.join().unwrap().unwrap().reason, Reason::Halted);
+        }
+        let (_tx, rx, _) = spawn(String::from("3,0,4,0,99"), Some(5));
+        assert_eq!(rx.recv(), Ok(5));
     }
 
     #[test]
-    fn test_output_large() {
-        let data = String::from("104,1125899906842624,99");
-        let (_tx, rx, _) = spawn(data.clone(), None);
-        let output = rx.recv().unwrap();
-        assert_eq!(output, "1125899906842624".to_string());
+    fn test_overflow() {
+        let data = "1102,4611686018427387904,2,7,4,7,99,0";
+        let mut p = Machine::new(create_memory(data).unwrap());
+        assert_eq!(p.run_until_io(), Ok(State::Output(i64::MIN)));
+
+        let mut p = Machine::new(create_memory(data).unwrap());
+        p.arithmetic(Arithmetic::Checked);
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::Overflow {
+                ptr: 0,
+                code: 1102,
+                a: 4611686018427387904,
+                b: 2
+            })
+        );
+
+        let mut p = Machine::new(create_memory("109,9223372036854775807,109,1,99").unwrap());
+        p.arithmetic(Arithmetic::Checked);
+        assert!(matches!(
+            p.run_until_io(),
+            Err(IntcodeError::Overflow { ptr: 2, .. })
+        ));
+
+        // relative address past i64::MAX
+        let data = "109,9223372036854775807,204,1,99";
+        let mut p = Machine::new(create_memory(data).unwrap());
+        p.arithmetic(Arithmetic::Checked);
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::Overflow {
+                ptr: 2,
+                code: 204,
+                a: i64::MAX,
+                b: 1
+            })
+        );
+        let mut p = Machine::new(create_memory(data).unwrap());
+        assert!(matches!(
+            p.run_until_io(),
+            Err(IntcodeError::NegativeAddress { ptr: 2, .. })
+        ));
     }
 }
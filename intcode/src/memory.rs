@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::ops::Index;
+
+use super::Memory;
+
+/// Words kept in one flat vector at the bottom of the address space.
+const DENSE: usize = 1 << 16;
+/// Words per page above `DENSE`.
+const PAGE: usize = 1 << 10;
+/// Default number of addressable words, see `Machine::limit_memory`.
+pub const DEFAULT_SIZE: usize = 1 << 40;
+
+static ZERO: i64 = 0;
+
+/// Sparse machine memory. Low addresses, where programs live, are stored in a
+/// flat vector, everything above in pages that are allocated on first write.
+/// Unmapped words read as 0. Two memories are equal if they have the same
+/// size and every address holds the same word, however they are stored.
+#[derive(Debug, Clone)]
+pub struct Ram {
+    words: Vec<i64>,
+    pages: HashMap<usize, Box<[i64; PAGE]>>,
+    size: usize,
+}
+
+impl Ram {
+    pub fn new(image: Memory, size: usize) -> Self {
+        Self {
+            words: image,
+            pages: HashMap::new(),
+            size,
+        }
+    }
+
+    /// Number of addressable words, addresses from `size` on are out of range.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn resize(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        if addr < self.words.len() {
+            return self.words[addr];
+        }
+        if addr < DENSE {
+            return 0;
+        }
+        match self.pages.get(&(addr / PAGE)) {
+            Some(page) => page[addr % PAGE],
+            None => 0,
+        }
+    }
+
+    /// Stores `value` at `addr`, the caller checks `addr` against `size`.
+    pub fn set(&mut self, addr: usize, value: i64) {
+        if addr < self.words.len() {
+            self.words[addr] = value;
+        } else if addr < DENSE {
+            self.words.resize(addr + 1, 0);
+            self.words[addr] = value;
+        } else {
+            let page = self
+                .pages
+                .entry(addr / PAGE)
+                .or_insert_with(|| Box::new([0; PAGE]));
+            page[addr % PAGE] = value;
+        }
+    }
+
+    /// One past the highest mapped address.
+    pub fn len(&self) -> usize {
+        match self.pages.keys().max() {
+            Some(page) => self.words.len().max((page + 1) * PAGE),
+            None => self.words.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mapped memory as `(start, words)` runs in address order. Pages only
+    /// contribute the words above the program image, words they cover below
+    /// it are never read.
+    pub(crate) fn segments(&self) -> Vec<(usize, &[i64])> {
+        let image = self.words.len();
+        let mut pages: Vec<(usize, &[i64])> = self
+            .pages
+            .iter()
+            .map(|(page, words)| (page * PAGE, &words[..]))
+            .filter(|(start, _)| start + PAGE > image)
+            .map(|(start, words)| {
+                let skip = image.saturating_sub(start);
+                (start + skip, &words[skip..])
+            })
+            .collect();
+        pages.sort_by_key(|(start, _)| *start);
+        let mut segments = vec![(0, &self.words[..])];
+        segments.extend(pages);
+        segments
+    }
+
+    /// Copies the words in `from..to`.
+    pub fn range(&self, from: usize, to: usize) -> Memory {
+        (from..to).map(|addr| self.get(addr)).collect()
+    }
+
+    /// Flattens all mapped memory into a vector. Beware of programs that
+    /// wrote to very high addresses.
+    pub fn to_vec(&self) -> Memory {
+        self.range(0, self.len())
+    }
+}
+
+impl PartialEq for Ram {
+    fn eq(&self, other: &Ram) -> bool {
+        let covers = |a: &Ram, b: &Ram| {
+            b.segments().into_iter().all(|(start, words)| {
+                words
+                    .iter()
+                    .enumerate()
+                    .all(|(offset, word)| a.get(start + offset) == *word)
+            })
+        };
+        self.size == other.size && covers(self, other) && covers(other, self)
+    }
+}
+
+impl Eq for Ram {}
+
+impl Index<usize> for Ram {
+    type Output = i64;
+    fn index(&self, addr: usize) -> &i64 {
+        if addr < self.words.len() {
+            return &self.words[addr];
+        }
+        if addr < DENSE {
+            return &ZERO;
+        }
+        match self.pages.get(&(addr / PAGE)) {
+            Some(page) => &page[addr % PAGE],
+            None => &ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_ram() {
+        let mut ram = Ram::new(vec![1, 2, 3], DEFAULT_SIZE);
+        assert_eq!(ram.len(), 3);
+        assert_eq!(ram.get(2), 3);
+        assert_eq!(ram.get(3), 0);
+        assert_eq!(ram.get(1_000_000_000_000), 0);
+
+        ram.set(10, 7);
+        assert_eq!(ram.to_vec(), vec![1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 7]);
+
+        ram.set(1_000_000_000_000, 42);
+        assert_eq!(ram.get(1_000_000_000_000), 42);
+        assert_eq!(ram[1_000_000_000_000], 42);
+        assert_eq!(ram[1_000_000_000_001], 0);
+        assert_eq!(ram.len(), 1_000_000_000_000 / PAGE * PAGE + PAGE);
+        assert_eq!(ram.range(9, 12), vec![0, 7, 0]);
+    }
+
+    #[test]
+    fn test_image_above_dense() {
+        let mut image = vec![0; 70000];
+        image[69700] = 42;
+        let mut ram = Ram::new(image.clone(), DEFAULT_SIZE);
+        ram.set(70500, 7);
+        let segments = ram.segments();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].0, 70000);
+        assert_eq!(ram.len(), (70500 / PAGE + 1) * PAGE);
+
+        let mut copy = Ram::new(Vec::new(), DEFAULT_SIZE);
+        for (start, words) in segments {
+            for (offset, word) in words.iter().enumerate() {
+                copy.set(start + offset, *word);
+            }
+        }
+        assert_eq!(copy.get(69700), 42);
+        assert_eq!(copy, ram);
+
+        let mut grown = Ram::new(image, DEFAULT_SIZE);
+        assert_ne!(grown, ram);
+        grown.set(70500, 7);
+        assert_eq!(grown, ram);
+        grown.set(70001, 0);
+        assert_eq!(grown, ram);
+    }
+}
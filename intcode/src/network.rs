@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+
+use super::{IntcodeError, Machine, State};
+
+/// A message between machines, written by a program as three outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// What the network reports to the handler passed to `Network::run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A packet for an address that no machine in the network has.
+    Packet(Packet),
+    /// Every machine asked for input, got none and sent nothing for a whole round.
+    Idle,
+}
+
+/// The handler's answer to an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    /// Delivers a packet into the network, then continues.
+    Send(Packet),
+    Stop,
+}
+
+struct Node {
+    machine: Machine,
+    queue: VecDeque<(i64, i64)>,
+    output: Vec<i64>,
+    halted: bool,
+}
+
+/// Packet switched network of machines running copies of one program.
+///
+/// Machine `n` reads `n` as its first input, afterwards every input is
+/// either the `x` and `y` of a queued packet or -1 when its queue is empty.
+/// Outputs are grouped into `(dest, x, y)` triples and routed to machine
+/// `dest`. Machines take turns, a turn feeds one packet, or -1, and runs
+/// the machine until it asks for input again. Packets still queued then wait
+/// for its next turn.
+pub struct Network {
+    nodes: Vec<Node>,
+}
+
+impl Network {
+    /// Boots `count` copies of `prototype`.
+    pub fn new(prototype: &Machine, count: usize) -> Self {
+        let nodes = (0..count)
+            .map(|addr| {
+                let mut machine = prototype.clone();
+                machine.input(addr as i64);
+                Node {
+                    machine,
+                    queue: VecDeque::new(),
+                    output: Vec::with_capacity(3),
+                    halted: false,
+                }
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Queues `packet` for its destination, returns it back if the address
+    /// belongs to no machine.
+    pub fn send(&mut self, packet: Packet) -> Option<Packet> {
+        if packet.dest < 0 || packet.dest as usize >= self.nodes.len() {
+            return Some(packet);
+        }
+        self.nodes[packet.dest as usize]
+            .queue
+            .push_back((packet.x, packet.y));
+        None
+    }
+
+    /// Gives machine `addr` its turn, returns the packets it sent and whether
+    /// it went without input.
+    fn turn(&mut self, addr: usize) -> Result<(Vec<Packet>, bool), IntcodeError> {
+        let node = &mut self.nodes[addr];
+        let mut sent = Vec::new();
+        let mut fed = false;
+        let mut starved = false;
+        loop {
+            match node.machine.run_until_io()? {
+                State::Output(value) => {
+                    node.output.push(value);
+                    if node.output.len() == 3 {
+                        sent.push(Packet {
+                            dest: node.output[0],
+                            x: node.output[1],
+                            y: node.output[2],
+                        });
+                        node.output.clear();
+                    }
+                }
+                State::Input if fed => break,
+                State::Input => {
+                    fed = true;
+                    match node.queue.pop_front() {
+                        Some((x, y)) => {
+                            node.machine.input(x);
+                            node.machine.input(y);
+                        }
+                        None => {
+                            node.machine.input(-1);
+                            starved = true;
+                        }
+                    }
+                }
+                State::Halted => {
+                    node.halted = true;
+                    break;
+                }
+            }
+        }
+        Ok((sent, starved))
+    }
+
+    /// Runs all machines until the handler says `Control::Stop` or every
+    /// machine halted. The handler sees packets to unknown addresses and
+    /// idle rounds.
+    pub fn run<F>(&mut self, mut handler: F) -> Result<(), IntcodeError>
+    where
+        F: FnMut(Event) -> Control,
+    {
+        let mut handle = |network: &mut Network, event: Event| match handler(event) {
+            Control::Continue => true,
+            Control::Send(packet) => {
+                if let Some(packet) = network.send(packet) {
+                    warn!("network: dropped {:?}", packet);
+                }
+                true
+            }
+            Control::Stop => false,
+        };
+        loop {
+            let mut idle = true;
+            let mut running = false;
+            for addr in 0..self.nodes.len() {
+                if self.nodes[addr].halted {
+                    continue;
+                }
+                running = true;
+                let (sent, starved) = self.turn(addr)?;
+                idle = idle && starved && sent.is_empty();
+                for packet in sent {
+                    debug!("network: {} -> {:?}", addr, packet);
+                    if let Some(packet) = self.send(packet) {
+                        if !handle(self, Event::Packet(packet)) {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            if !running {
+                return Ok(());
+            }
+            let queued = self.nodes.iter().any(|node| !node.queue.is_empty());
+            if idle && !queued && !handle(self, Event::Idle) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    const NIC: &str = "
+                IN [addr]
+                JT [addr], #loop    ; machine 0 starts the conversation
+                OUT #1
+                OUT #5
+                OUT #7
+        loop:   IN [x]
+                EQ [x], #-1, [tmp]
+                JT [tmp], #loop
+                IN [y]
+                ADD [x], [addr], [x]
+                OUT #255
+                OUT [x]
+                OUT [y]
+                JT #1, #loop
+        addr:   DATA 0
+        x:      DATA 0
+        y:      DATA 0
+        tmp:    DATA 0
+    ";
+
+    #[test]
+    fn test_network() {
+        let nic = Machine::new(assemble(NIC).unwrap());
+        let mut network = Network::new(&nic, 3);
+        let mut events = Vec::new();
+        let mut nat = None;
+        network
+            .run(|event| {
+                events.push(event);
+                match event {
+                    Event::Packet(packet) if nat.is_none() => {
+                        nat = Some(packet);
+                        Control::Continue
+                    }
+                    Event::Packet(_) => Control::Stop,
+                    Event::Idle => Control::Send(Packet {
+                        dest: 2,
+                        x: 100,
+                        y: 1,
+                    }),
+                }
+            })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Packet(Packet {
+                    dest: 255,
+                    x: 6,
+                    y: 7
+                }),
+                Event::Idle,
+                Event::Packet(Packet {
+                    dest: 255,
+                    x: 102,
+                    y: 1
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_halted_network() {
+        let echo = Machine::new(assemble("IN [0]\nOUT [0]\nHLT").unwrap());
+        let mut network = Network::new(&echo, 2);
+        network.run(|_| Control::Continue).unwrap();
+    }
+}
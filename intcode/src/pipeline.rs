@@ -0,0 +1,107 @@
+use super::{IntcodeError, Machine, State};
+
+/// Clones `prototype` once per seed and queues the seed values as the first
+/// inputs of the copy, e.g. the phase settings of amplifiers.
+pub fn seeded<S: AsRef<[i64]>>(prototype: &Machine, seeds: &[S]) -> Vec<Machine> {
+    seeds
+        .iter()
+        .map(|seed| {
+            let mut machine = prototype.clone();
+            for value in seed.as_ref() {
+                machine.input(*value);
+            }
+            machine
+        })
+        .collect()
+}
+
+fn run(mut stages: Vec<Machine>, input: &[i64], ring: bool) -> Result<Option<i64>, IntcodeError> {
+    let count = stages.len();
+    if count == 0 {
+        return Ok(None);
+    }
+    for value in input {
+        stages[0].input(*value);
+    }
+    let mut last = None;
+    loop {
+        let before: u64 = stages.iter().map(|m| m.instructions()).sum();
+        for i in 0..count {
+            while let State::Output(value) = stages[i].run_until_io()? {
+                if i + 1 < count {
+                    stages[i + 1].input(value);
+                } else {
+                    last = Some(value);
+                    if ring {
+                        stages[0].input(value);
+                    }
+                }
+            }
+        }
+        if stages.iter().all(|m| m.halted()) {
+            return Ok(last);
+        }
+        let after: u64 = stages.iter().map(|m| m.instructions()).sum();
+        if before == after {
+            let stuck = stages.iter().find(|m| !m.halted()).unwrap();
+            return Err(IntcodeError::InputClosed {
+                ptr: stuck.ptr,
+                code: stuck.code(),
+            });
+        }
+    }
+}
+
+/// Feeds `input` to the first stage and the outputs of every stage to the
+/// next one. Returns the last value of the final stage once all halted.
+/// Stages that wait for input nobody can provide fail with
+/// `IntcodeError::InputClosed`.
+pub fn series(stages: Vec<Machine>, input: &[i64]) -> Result<Option<i64>, IntcodeError> {
+    run(stages, input, false)
+}
+
+/// Like `series`, but outputs of the final stage are also fed back into the
+/// first one.
+pub fn feedback(stages: Vec<Machine>, input: &[i64]) -> Result<Option<i64>, IntcodeError> {
+    run(stages, input, true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_memory;
+
+    fn amplifier(data: &str) -> Machine {
+        Machine::new(create_memory(data).unwrap())
+    }
+
+    #[test]
+    fn test_series() {
+        let amp = amplifier("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let stages = seeded(&amp, &[[4], [3], [2], [1], [0]]);
+        assert_eq!(series(stages, &[0]), Ok(Some(43210)));
+
+        let amp =
+            amplifier("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0");
+        let stages = seeded(&amp, &[[0], [1], [2], [3], [4]]);
+        assert_eq!(series(stages, &[0]), Ok(Some(54321)));
+    }
+
+    #[test]
+    fn test_feedback() {
+        let amp = amplifier(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        );
+        let stages = seeded(&amp, &[[9], [8], [7], [6], [5]]);
+        assert_eq!(feedback(stages, &[0]), Ok(Some(139629729)));
+    }
+
+    #[test]
+    fn test_stuck() {
+        let stages = vec![amplifier("3,0,3,0,99")];
+        assert_eq!(
+            series(stages, &[1]),
+            Err(IntcodeError::InputClosed { ptr: 2, code: 3 })
+        );
+    }
+}
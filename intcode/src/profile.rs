@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::disasm::{decode, MNEMONICS};
+use super::{opcode, Machine, Ram};
+
+/// Execution counts collected while a machine runs, see `Machine::profile`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Executions per instruction address.
+    pub addresses: HashMap<usize, u64>,
+    /// Executions per opcode.
+    pub opcodes: BTreeMap<u8, u64>,
+    /// Highest address an instruction or operand touched.
+    pub max_address: usize,
+    /// Number of executed `ARB` instructions.
+    pub rel_adjustments: u64,
+    /// Taken backward jumps per `(jump address, target)`.
+    pub jumps: HashMap<(usize, usize), u64>,
+}
+
+/// A backward jump and the code it repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    pub start: usize,
+    /// Address of the jump that closes the loop.
+    pub end: usize,
+    pub iterations: u64,
+    /// Instructions executed between `start` and `end`, nested loops included.
+    pub instructions: u64,
+}
+
+impl Profile {
+    pub(crate) fn count(&mut self, ptr: usize, code: i64, next: usize) {
+        let (op, _) = opcode(code);
+        *self.addresses.entry(ptr).or_insert(0) += 1;
+        *self.opcodes.entry(op).or_insert(0) += 1;
+        self.max_address = self.max_address.max(ptr);
+        match op {
+            9 => self.rel_adjustments += 1,
+            5 | 6 if next <= ptr => *self.jumps.entry((ptr, next)).or_insert(0) += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn touch(&mut self, addr: usize) {
+        self.max_address = self.max_address.max(addr);
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.opcodes.values().sum()
+    }
+
+    /// Loops ranked by the number of instructions executed inside them.
+    pub fn hot_loops(&self) -> Vec<Loop> {
+        let mut loops: Vec<Loop> = self
+            .jumps
+            .iter()
+            .map(|((end, start), iterations)| Loop {
+                start: *start,
+                end: *end,
+                iterations: *iterations,
+                instructions: self
+                    .addresses
+                    .iter()
+                    .filter(|(addr, _)| (start..=end).contains(addr))
+                    .map(|(_, count)| count)
+                    .sum(),
+            })
+            .collect();
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.instructions), l.start));
+        loops
+    }
+
+    /// Text report with the opcode histogram and the `top` loops and
+    /// addresses, instructions are disassembled from `memory`.
+    pub fn report(&self, memory: &Ram, top: usize) -> String {
+        let total = self.instructions().max(1);
+        let percent = |count: u64| count as f64 * 100.0 / total as f64;
+        let mut lines = vec![format!(
+            "instructions: {}, max address: {}, relative base adjustments: {}",
+            self.instructions(),
+            self.max_address,
+            self.rel_adjustments
+        )];
+
+        lines.push("opcodes:".to_string());
+        let mut opcodes: Vec<(&u8, &u64)> = self.opcodes.iter().collect();
+        opcodes.sort_by_key(|(op, count)| (std::cmp::Reverse(**count), **op));
+        for (op, count) in opcodes {
+            let name = MNEMONICS
+                .iter()
+                .find(|(code, _, _)| code == op)
+                .map_or("???", |(_, name, _)| name);
+            lines.push(format!(
+                "  {:<4}{:>12} {:>6.2}%",
+                name,
+                count,
+                percent(*count)
+            ));
+        }
+
+        lines.push("hot loops:".to_string());
+        for l in self.hot_loops().iter().take(top) {
+            lines.push(format!(
+                "  {:04}..{:04} {:>10} iterations {:>12} instructions {:>6.2}%",
+                l.start,
+                l.end,
+                l.iterations,
+                l.instructions,
+                percent(l.instructions)
+            ));
+        }
+
+        lines.push("hot addresses:".to_string());
+        let mut addresses: Vec<(&usize, &u64)> = self.addresses.iter().collect();
+        addresses.sort_by_key(|(addr, count)| (std::cmp::Reverse(**count), **addr));
+        for (addr, count) in addresses.into_iter().take(top) {
+            let window = memory.range(*addr, addr + 4);
+            let text = decode(&window, 0).map_or_else(|| format!("DATA {}", window[0]), |d| d.0);
+            lines.push(format!(
+                "  {:04}{:>12} {:>6.2}%  {}",
+                addr,
+                count,
+                percent(*count),
+                text
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Machine {
+    /// Starts counting executions per address and opcode.
+    pub fn profile(&mut self) {
+        self.profile.get_or_insert_with(Profile::default);
+    }
+
+    pub fn profiler(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_memory;
+    use crate::State;
+    #[test]
+    fn test_profile() {
+        // counts down from 3 in a loop between 0002 and 0006
+        let data = "109,100,1001,12,-1,12,1005,12,2,4,12,99,3";
+        let mut p = Machine::new(create_memory(data).unwrap());
+        p.profile();
+        assert_eq!(p.run_until_io(), Ok(State::Output(0)));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        let profile = p.profiler().unwrap();
+        assert_eq!(profile.instructions(), 9);
+        assert_eq!(profile.addresses[&2], 3);
+        assert_eq!(profile.opcodes[&5], 3);
+        assert_eq!(profile.rel_adjustments, 1);
+        assert_eq!(profile.max_address, 12);
+        assert_eq!(
+            profile.hot_loops(),
+            vec![Loop {
+                start: 2,
+                end: 6,
+                iterations: 2,
+                instructions: 6
+            }]
+        );
+        let report = profile.report(&p.memory, 1);
+        assert!(report.starts_with("instructions: 9, max address: 12"));
+        assert!(
+            report.contains("  0002..0006          2 iterations            6 instructions  66.67%")
+        );
+        assert!(report.contains("  0002           3  33.33%  ADD [12], #-1, [12]"));
+    }
+}
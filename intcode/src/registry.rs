@@ -0,0 +1,179 @@
+//! Custom opcodes on top of the ten built-in instructions.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{mode, opcode, IntcodeError, Machine, Reason, State};
+
+/// An instruction implemented outside the crate. `execute` returns the
+/// address of the next instruction.
+pub trait Operation: Send + Sync {
+    fn execute(&self, ctx: &mut Context) -> Result<usize, IntcodeError>;
+}
+
+impl<F> Operation for F
+where
+    F: Fn(&mut Context) -> Result<usize, IntcodeError> + Send + Sync,
+{
+    fn execute(&self, ctx: &mut Context) -> Result<usize, IntcodeError> {
+        self(ctx)
+    }
+}
+
+/// Opcodes registered in addition to, or instead of, the built-in ones.
+#[derive(Clone, Default)]
+pub struct InstructionSet {
+    operations: HashMap<u8, Arc<dyn Operation>>,
+}
+
+impl InstructionSet {
+    /// The built-in instructions only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Executes `operation` for opcode `op`, replacing a built-in instruction
+    /// with the same number.
+    pub fn register<O: Operation + 'static>(&mut self, op: u8, operation: O) -> &mut Self {
+        self.operations.insert(op, Arc::new(operation));
+        self
+    }
+
+    pub fn contains(&self, op: u8) -> bool {
+        self.operations.contains_key(&op)
+    }
+
+    pub(crate) fn get(&self, code: i64) -> Option<Arc<dyn Operation>> {
+        if code < 0 {
+            return None;
+        }
+        let (op, _) = opcode(code);
+        self.operations.get(&op).cloned()
+    }
+}
+
+/// View of the machine handed to a custom instruction.
+pub struct Context<'a> {
+    pub(crate) machine: &'a mut Machine,
+}
+
+impl<'a> Context<'a> {
+    pub fn ptr(&self) -> usize {
+        self.machine.ptr
+    }
+
+    /// The instruction word being executed.
+    pub fn code(&self) -> i64 {
+        self.machine.code()
+    }
+
+    pub fn rel(&self) -> i64 {
+        self.machine.rel
+    }
+
+    pub fn set_rel(&mut self, rel: i64) {
+        self.machine.rel = rel;
+    }
+
+    /// Address of the 1-based parameter `n` resolved through its mode.
+    pub fn address(&mut self, n: usize) -> Result<usize, IntcodeError> {
+        let (_, modes) = opcode(self.code());
+        self.machine.get_address(mode(modes, n as u32), n)
+    }
+
+    /// Value of the 1-based parameter `n`.
+    pub fn param(&mut self, n: usize) -> Result<i64, IntcodeError> {
+        let addr = self.address(n)?;
+        Ok(self.machine.read(addr))
+    }
+
+    pub fn read(&mut self, addr: usize) -> i64 {
+        self.machine.read(addr)
+    }
+
+    /// Stores `value` at `addr`, use `address` to get a checked address.
+    pub fn write(&mut self, addr: usize, value: i64) {
+        self.machine.write(addr, value)
+    }
+
+    /// Takes the next queued input. Without one the machine waits for input
+    /// and the instruction should return `ptr()` to run again.
+    pub fn input(&mut self) -> Option<i64> {
+        let value = self.machine.next_input();
+        if value.is_none() {
+            self.machine.state = Some(State::Input);
+        }
+        value
+    }
+
+    /// Hands `value` to the caller once the instruction finished, only one
+    /// output per instruction.
+    pub fn output(&mut self, value: i64) {
+        self.machine.state = Some(State::Output(value));
+    }
+
+    /// Stops the machine like opcode 99.
+    pub fn halt(&mut self) {
+        self.machine.stop = Some(Reason::Halted);
+        self.machine.state = Some(State::Halted);
+    }
+}
+
+impl Machine {
+    /// Runs the machine with the custom opcodes of `set`.
+    pub fn instruction_set(&mut self, set: InstructionSet) {
+        self.custom = Some(set);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_memory;
+
+    fn machine(data: &str) -> Machine {
+        let mut set = InstructionSet::new();
+        // DBL a, b: stores twice the value of a in b
+        set.register(42, |ctx: &mut Context| {
+            let value = ctx.param(1)? * 2;
+            let addr = ctx.address(2)?;
+            ctx.write(addr, value);
+            Ok(ctx.ptr() + 3)
+        });
+        // INC: echoes the next input plus one
+        set.register(43, |ctx: &mut Context| match ctx.input() {
+            Some(value) => {
+                ctx.output(value + 1);
+                Ok(ctx.ptr() + 1)
+            }
+            None => Ok(ctx.ptr()),
+        });
+        // replaces HLT with a trap
+        set.register(99, |ctx: &mut Context| {
+            Err(IntcodeError::UnknownOpcode {
+                ptr: ctx.ptr(),
+                code: ctx.code(),
+            })
+        });
+        let mut p = Machine::new(create_memory(data).unwrap());
+        p.instruction_set(set);
+        p
+    }
+
+    #[test]
+    fn test_custom_opcodes() {
+        let mut p = machine("42,7,8,4,8,43,43,21,0");
+        assert_eq!(p.run_until_io(), Ok(State::Output(42)));
+        p.input(1);
+        assert_eq!(p.run_until_io(), Ok(State::Output(2)));
+        assert_eq!(p.run_until_io(), Ok(State::Input));
+        p.input(2);
+        assert_eq!(p.run_until_io(), Ok(State::Output(3)));
+        assert_eq!(p.instructions(), 4);
+
+        let mut p = machine("1101,1,1,0,99");
+        assert_eq!(
+            p.run_until_io(),
+            Err(IntcodeError::UnknownOpcode { ptr: 4, code: 99 })
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{IntcodeError, Machine, State};
+
+const HEADER: &str = "intcode session 1";
+
+/// One line of a session: an input the machine consumed, an output it
+/// produced or its halt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Input(i64),
+    Output(i64),
+    Halt,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Input(value) => write!(f, "in {}", value),
+            Event::Output(value) => write!(f, "out {}", value),
+            Event::Halt => write!(f, "halt"),
+        }
+    }
+}
+
+/// The I/O of a machine run, written by `Machine::record`:
+///
+/// ```text
+/// intcode session 1
+/// in 0
+/// out 1
+/// out 0
+/// halt
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub events: Vec<Event>,
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Session {
+    type Err = IntcodeError;
+
+    fn from_str(text: &str) -> Result<Self, IntcodeError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, line)) if line.trim() == HEADER => {}
+            other => {
+                return Err(IntcodeError::BadSession {
+                    line: 1,
+                    text: other.map(|(_, line)| line).unwrap_or("").to_string(),
+                })
+            }
+        }
+        let mut session = Session::default();
+        for (index, line) in lines {
+            let bad = || IntcodeError::BadSession {
+                line: index + 1,
+                text: line.to_string(),
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let event = match fields.as_slice() {
+                [] => continue,
+                ["in", value] => Event::Input(value.parse().map_err(|_| bad())?),
+                ["out", value] => Event::Output(value.parse().map_err(|_| bad())?),
+                ["halt"] => Event::Halt,
+                _ => return Err(bad()),
+            };
+            session.events.push(event);
+        }
+        Ok(session)
+    }
+}
+
+impl Session {
+    /// Reads a session file written by `Machine::record`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Feeds the recorded inputs to `machine` and checks that it produces the
+    /// recorded outputs and halt in the same order. Fails with
+    /// `IntcodeError::Replay` at the first event the machine deviates from.
+    pub fn replay(&self, machine: &mut Machine) -> Result<(), IntcodeError> {
+        for (index, expected) in self.events.iter().enumerate() {
+            let found = match expected {
+                Event::Input(value) => {
+                    machine.input(*value);
+                    continue;
+                }
+                Event::Output(value) => State::Output(*value),
+                Event::Halt => State::Halted,
+            };
+            let state = machine.run_until_io()?;
+            if state != found {
+                return Err(IntcodeError::Replay {
+                    event: index + 1,
+                    expected: *expected,
+                    found: state,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Machine {
+    /// Writes every consumed input, every output and the halt to `out`, one
+    /// line each, see `Session`. Recording stops if `out` fails.
+    pub fn record<W: Write + Send + 'static>(&mut self, out: W) {
+        let mut out: Box<dyn Write + Send> = Box::new(out);
+        match writeln!(out, "{}", HEADER) {
+            Ok(()) => self.session = Some(out),
+            Err(err) => warn!("session: cannot write header, not recording: {}", err),
+        }
+    }
+
+    /// Takes the next queued input and records it.
+    pub(crate) fn next_input(&mut self) -> Option<i64> {
+        let value = self.input.pop_front();
+        if let Some(value) = value {
+            self.log(Event::Input(value));
+        }
+        value
+    }
+
+    /// Lines are flushed right away, a session is still readable when the
+    /// driver never returns.
+    pub(crate) fn log(&mut self, event: Event) {
+        if let Some(out) = self.session.as_mut() {
+            if let Err(err) = writeln!(out, "{}", event).and_then(|_| out.flush()) {
+                warn!("session: cannot write event, recording stopped: {}", err);
+                self.session = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_memory;
+    use std::env;
+    use std::fs::File;
+
+    // paints like day11: reads a colour, outputs the input and a turn of 1
+    const ROBOT: &str = "3,100,4,100,104,1,3,100,4,100,104,1,99";
+
+    #[test]
+    fn test_record_replay() {
+        let path = env::temp_dir().join(format!("intcode-session-{}", std::process::id()));
+        let mut p = Machine::new(create_memory(ROBOT).unwrap());
+        p.record(File::create(&path).unwrap());
+        p.input(0);
+        assert_eq!(p.run_until_io(), Ok(State::Output(0)));
+        assert_eq!(p.run_until_io(), Ok(State::Output(1)));
+        assert_eq!(p.run_until_io(), Ok(State::Input));
+        p.input(1);
+        while p.run_until_io() != Ok(State::Halted) {}
+        let session = Session::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            session.to_string(),
+            "intcode session 1\nin 0\nout 0\nout 1\nin 1\nout 1\nout 1\nhalt\n"
+        );
+
+        let mut q = Machine::new(create_memory(ROBOT).unwrap());
+        assert_eq!(session.replay(&mut q), Ok(()));
+        assert!(q.halted());
+
+        // turns the other way on the second step
+        let mut q = Machine::new(create_memory(&ROBOT.replace("104,1,99", "104,0,99")).unwrap());
+        assert_eq!(
+            session.replay(&mut q),
+            Err(IntcodeError::Replay {
+                event: 6,
+                expected: Event::Output(1),
+                found: State::Output(0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_session_text() {
+        let session: Session = "intcode session 1\nin -3\n\nout 4\nhalt\n".parse().unwrap();
+        assert_eq!(
+            session.events,
+            vec![Event::Input(-3), Event::Output(4), Event::Halt]
+        );
+        let mut p = Machine::new(create_memory("3,0,4,0,99").unwrap());
+        assert_eq!(
+            session.replay(&mut p),
+            Err(IntcodeError::Replay {
+                event: 2,
+                expected: Event::Output(4),
+                found: State::Output(-3),
+            })
+        );
+        assert_eq!(
+            "intcode session 1\nout x\n".parse::<Session>(),
+            Err(IntcodeError::BadSession {
+                line: 2,
+                text: "out x".to_string()
+            })
+        );
+        assert_eq!(
+            "in 1\n".parse::<Session>(),
+            Err(IntcodeError::BadSession {
+                line: 1,
+                text: "in 1".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{memory, IntcodeError, Machine, Ram, Reason};
+
+const HEADER: &str = "intcode snapshot 1";
+
+/// Complete state of a paused machine: memory, pointer, relative base,
+/// queued input and instruction count. Tracers and limits are not part of it.
+///
+/// The text form written by `Display` and read by `FromStr` is line based:
+///
+/// ```text
+/// intcode snapshot 1
+/// ptr 4
+/// rel 0
+/// instructions 1
+/// halted false
+/// input 8
+/// size 1099511627776
+/// memory 0 3,9,8,9,10,9,4,9,99,8,8
+/// ```
+///
+/// with one `memory <start> <words>` line per mapped region. Without a `size`
+/// line the memory gets `memory::DEFAULT_SIZE` words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub ptr: usize,
+    pub rel: i64,
+    pub instructions: u64,
+    pub halted: bool,
+    pub input: Vec<i64>,
+    pub memory: Ram,
+}
+
+fn join(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+    values.join(",")
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "ptr {}", self.ptr)?;
+        writeln!(f, "rel {}", self.rel)?;
+        writeln!(f, "instructions {}", self.instructions)?;
+        writeln!(f, "halted {}", self.halted)?;
+        writeln!(f, "input {}", join(&self.input))?;
+        writeln!(f, "size {}", self.memory.size())?;
+        for (start, words) in self.memory.segments() {
+            writeln!(f, "memory {} {}", start, join(words))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = IntcodeError;
+
+    fn from_str(text: &str) -> Result<Self, IntcodeError> {
+        let mut snapshot = Snapshot {
+            ptr: 0,
+            rel: 0,
+            instructions: 0,
+            halted: false,
+            input: Vec::new(),
+            memory: Ram::new(Vec::new(), memory::DEFAULT_SIZE),
+        };
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, line)) if line.trim() == HEADER => {}
+            other => {
+                return Err(IntcodeError::BadSnapshot {
+                    line: 1,
+                    text: other.map(|(_, line)| line).unwrap_or("").to_string(),
+                })
+            }
+        }
+        for (index, line) in lines {
+            let bad = || IntcodeError::BadSnapshot {
+                line: index + 1,
+                text: line.to_string(),
+            };
+            let mut fields = line.split_whitespace();
+            let key = match fields.next() {
+                Some(key) => key,
+                None => continue,
+            };
+            let value = fields.next().unwrap_or("");
+            let list = |text: &str| -> Result<Vec<i64>, IntcodeError> {
+                if text.is_empty() {
+                    return Ok(Vec::new());
+                }
+                text.split(',')
+                    .map(|x| x.parse().map_err(|_| bad()))
+                    .collect()
+            };
+            match key {
+                "ptr" => snapshot.ptr = value.parse().map_err(|_| bad())?,
+                "rel" => snapshot.rel = value.parse().map_err(|_| bad())?,
+                "instructions" => snapshot.instructions = value.parse().map_err(|_| bad())?,
+                "halted" => snapshot.halted = value.parse().map_err(|_| bad())?,
+                "input" => snapshot.input = list(value)?,
+                "size" => snapshot.memory.resize(value.parse().map_err(|_| bad())?),
+                "memory" => {
+                    let start: usize = value.parse().map_err(|_| bad())?;
+                    let words = list(fields.next().unwrap_or(""))?;
+                    for (offset, word) in words.into_iter().enumerate() {
+                        let addr = start.checked_add(offset).ok_or_else(bad)?;
+                        snapshot.memory.set(addr, word);
+                    }
+                }
+                _ => return Err(bad()),
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+impl Clone for Machine {
+    /// Copies the complete machine state and instruction set, the clone has no
+    /// tracer, no profile and no session recorder.
+    fn clone(&self) -> Self {
+        let mut machine = Machine::restore(self.snapshot());
+        machine.stop = self.stop;
+        machine.max_instructions = self.max_instructions;
+        machine.max_time = self.max_time;
+        machine.started = self.started;
+        machine.custom = self.custom.clone();
+        machine.arithmetic = self.arithmetic;
+        machine
+    }
+}
+
+impl Machine {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ptr: self.ptr,
+            rel: self.rel,
+            instructions: self.instructions,
+            halted: self.halted(),
+            input: self.input.iter().copied().collect(),
+            memory: self.memory.clone(),
+        }
+    }
+
+    /// Builds a machine that continues where the snapshot was taken.
+    pub fn restore(snapshot: Snapshot) -> Self {
+        let mut machine = Machine::new(Vec::new());
+        machine.memory = snapshot.memory;
+        machine.ptr = snapshot.ptr;
+        machine.rel = snapshot.rel;
+        machine.instructions = snapshot.instructions;
+        machine.input = snapshot.input.into_iter().collect();
+        if snapshot.halted {
+            machine.stop = Some(Reason::Halted);
+        }
+        machine
+    }
+
+    /// Writes a snapshot of the machine to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.snapshot().to_string())
+    }
+
+    /// Restores a machine from a snapshot file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let snapshot: Snapshot = fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Machine::restore(snapshot))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{create_memory, State};
+    use std::env;
+
+    fn eq8() -> Machine {
+        let mut p = Machine::new(create_memory("3,9,8,9,10,9,4,9,99,-1,8").unwrap());
+        p.input(8);
+        p.step().unwrap();
+        p
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut p = Machine::new(create_memory("3,9,8,9,10,9,4,9,99,-1,8").unwrap());
+        assert_eq!(p.run_until_io(), Ok(State::Input));
+        let mut q = p.clone();
+        p.input(8);
+        q.input(7);
+        assert_eq!(p.run_until_io(), Ok(State::Output(1)));
+        assert_eq!(q.run_until_io(), Ok(State::Output(0)));
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        assert_eq!(p.clone().run_until_io(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn test_snapshot_text() {
+        let mut p = eq8();
+        p.input(5);
+        let text = p.snapshot().to_string();
+        assert_eq!(
+            text,
+            "intcode snapshot 1\nptr 2\nrel 0\ninstructions 1\nhalted false\ninput 5\n\
+             size 1099511627776\nmemory 0 3,9,8,9,10,9,4,9,99,8,8\n"
+        );
+        let snapshot: Snapshot = text.parse().unwrap();
+        assert_eq!(snapshot, p.snapshot());
+        let mut q = Machine::restore(snapshot);
+        assert_eq!(q.run_until_io(), Ok(State::Output(1)));
+
+        assert_eq!(
+            "intcode snapshot 1\nptr x\n".parse::<Snapshot>(),
+            Err(IntcodeError::BadSnapshot {
+                line: 2,
+                text: "ptr x".to_string()
+            })
+        );
+        let line = format!("memory {} 1,2", usize::MAX);
+        assert_eq!(
+            format!("intcode snapshot 1\n{}\n", line).parse::<Snapshot>(),
+            Err(IntcodeError::BadSnapshot {
+                line: 2,
+                text: line
+            })
+        );
+
+        // without a size line the memory has the default size
+        let snapshot: Snapshot = "intcode snapshot 1\nmemory 0 104,7,99\n".parse().unwrap();
+        assert_eq!(snapshot.memory.size(), memory::DEFAULT_SIZE);
+        let mut q = Machine::restore(snapshot);
+        assert_eq!(q.run_until_io(), Ok(State::Output(7)));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut p = Machine::new(create_memory("21101,6,7,1000000000000,99").unwrap());
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        let path = env::temp_dir().join(format!("intcode-snapshot-{}", std::process::id()));
+        p.save(&path).unwrap();
+        let q = Machine::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(q.halted());
+        assert_eq!(q.snapshot(), p.snapshot());
+        assert_eq!(q.exit().unwrap().memory[1000000000000], 13);
+    }
+
+    #[test]
+    fn test_large_image() {
+        // a 70000 word image, the page written at 70500 also covers 69632..
+        let mut image = vec![0; 70000];
+        image[0..5].copy_from_slice(&[1101, 6, 7, 70500, 99]);
+        image[69700] = 42;
+        let mut p = Machine::new(image);
+        assert_eq!(p.run_until_io(), Ok(State::Halted));
+        let snapshot: Snapshot = p.snapshot().to_string().parse().unwrap();
+        assert_eq!(snapshot, p.snapshot());
+        let q = Machine::restore(snapshot);
+        assert_eq!(q.peek(69700), 42);
+        assert_eq!(q.peek(70500), 13);
+    }
+}
@@ -0,0 +1,112 @@
+//! String adapter over `spawn` for drivers that pass values around as text,
+//! every line on the channels holds a single decimal value.
+use std::panic;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{create_memory, start, Exit, IntcodeError, Machine};
+
+type Spawned = (
+    Sender<String>,
+    Receiver<String>,
+    thread::JoinHandle<Result<Exit, IntcodeError>>,
+);
+
+/// Puts threads that convert lines to values and back in front of the
+/// channels of a spawned machine. A line that is not a number closes the
+/// input and the machine ends with that `IntcodeError::Parse`.
+fn adapt((input, output, machine): super::Spawned, init: Option<String>) -> Spawned {
+    let (tx, lines) = channel::<String>();
+    let (values, rx) = channel();
+    let error = Arc::new(Mutex::new(None));
+    let parse_error = Arc::clone(&error);
+    thread::spawn(move || {
+        for (index, line) in lines.iter().enumerate() {
+            match line.trim().parse() {
+                Ok(value) => {
+                    if input.send(value).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => {
+                    *parse_error.lock().unwrap() = Some(IntcodeError::Parse { index, token: line });
+                    break;
+                }
+            }
+        }
+    });
+    thread::spawn(move || {
+        for value in output.iter() {
+            if values.send(value.to_string()).is_err() {
+                break;
+            }
+        }
+    });
+    let handle = thread::spawn(move || {
+        let result = machine
+            .join()
+            .unwrap_or_else(|err| panic::resume_unwind(err));
+        match result {
+            Err(IntcodeError::InputClosed { .. }) => match error.lock().unwrap().take() {
+                Some(err) => Err(err),
+                None => result,
+            },
+            _ => result,
+        }
+    });
+    if let Some(line) = init {
+        tx.send(line).unwrap();
+    }
+    (tx, rx, handle)
+}
+
+pub fn spawn(data: String, init: Option<String>) -> Spawned {
+    adapt(
+        start(None, move || Ok(Machine::new(create_memory(&data)?))),
+        init,
+    )
+}
+
+/// Like `spawn` for a machine that was set up beforehand.
+pub fn spawn_machine(machine: Machine, init: Option<String>) -> Spawned {
+    adapt(super::spawn_machine(machine, None), init)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_text_spawn() {
+        let data = String::from("3,9,8,9,10,9,4,9,99,-1,8");
+        let (_tx, rx, _) = spawn(data.clone(), Some(" 8\n".to_string()));
+        assert_eq!(rx.recv().expect("cannot recv"), "1");
+
+        let data = String::from("104,1125899906842624,99");
+        let (_tx, rx, _) = spawn(data, None);
+        assert_eq!(rx.recv().expect("cannot recv"), "1125899906842624");
+    }
+    #[test]
+    fn test_text_parse_error() {
+        let data = String::from("3,0,3,0,99");
+        let (_tx, _rx, handle) = spawn(data, Some("eight".to_string()));
+        let err = handle.join().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            IntcodeError::Parse {
+                index: 0,
+                token: "eight".to_string()
+            }
+        );
+    }
+    #[test]
+    fn test_text_spawn_machine() {
+        let mut p = Machine::new(create_memory("3,0,4,0,3,0,4,0,99").unwrap());
+        p.input(7);
+        let (tx, rx, handle) = spawn_machine(p, None);
+        assert_eq!(rx.recv().expect("cannot recv"), "7");
+        tx.send("-2".to_string()).unwrap();
+        assert_eq!(rx.recv().expect("cannot recv"), "-2");
+        assert_eq!(handle.join().unwrap().unwrap().instructions, 5);
+    }
+}
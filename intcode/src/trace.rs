@@ -0,0 +1,73 @@
+//! One JSON record per executed instruction, see `Machine::trace`.
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Record {
+    pub ptr: usize,
+    pub code: i64,
+    /// Relative base the instruction was executed with.
+    pub rel: i64,
+    pub modes: Vec<u32>,
+    pub addrs: Vec<usize>,
+    pub reads: Vec<i64>,
+    pub write: Option<(usize, i64)>,
+}
+
+fn list<T: ToString>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+impl Record {
+    pub fn new(ptr: usize, code: i64, rel: i64) -> Self {
+        Self {
+            ptr,
+            code,
+            rel,
+            ..Default::default()
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write!(
+            out,
+            "{{\"ptr\":{},\"op\":{},\"modes\":{},\"addrs\":{},\"reads\":{},\"write\":",
+            self.ptr,
+            self.code % 100,
+            list(&self.modes),
+            list(&self.addrs),
+            list(&self.reads)
+        )
+        .unwrap();
+        match self.write {
+            Some((addr, value)) => write!(out, "{{\"addr\":{},\"value\":{}}}", addr, value),
+            None => write!(out, "null"),
+        }
+        .unwrap();
+        write!(out, ",\"rel\":{}}}", self.rel).unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_to_json() {
+        let mut record = Record::new(4, 1002, 7);
+        record.modes = vec![0, 1, 0];
+        record.addrs = vec![4, 6, 4];
+        record.reads = vec![33, 3];
+        record.write = Some((4, 99));
+        assert_eq!(
+            record.to_json(),
+            "{\"ptr\":4,\"op\":2,\"modes\":[0,1,0],\"addrs\":[4,6,4],\"reads\":[33,3],\
+             \"write\":{\"addr\":4,\"value\":99},\"rel\":7}"
+        );
+        assert_eq!(
+            Record::new(8, 99, 0).to_json(),
+            "{\"ptr\":8,\"op\":99,\"modes\":[],\"addrs\":[],\"reads\":[],\"write\":null,\"rel\":0}"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Runs every case file in `tests/cases`. A case holds `key: value` lines,
+//! `#` starts a comment:
+//!
+//! ```text
+//! program: 3,9,8,9,10,9,4,9,99,-1,8
+//! input: 8
+//! output: 1
+//! memory: 3,9,8,9,10,9,4,9,99,1,8
+//! ```
+//!
+//! `program` may be split over several lines, `input` and `memory` are
+//! optional. `memory` is compared with the words from address 0 on after the
+//! program halted.
+extern crate intcode;
+
+use std::fs;
+use std::path::Path;
+
+use intcode::{create_memory, Machine, Memory, State};
+
+const LIMIT: u64 = 10_000_000;
+
+#[derive(Debug, Default)]
+struct Case {
+    program: Vec<String>,
+    input: Memory,
+    output: Option<Memory>,
+    memory: Option<Memory>,
+}
+
+fn values(text: &str) -> Result<Memory, String> {
+    if text.trim().is_empty() {
+        return Ok(Memory::new());
+    }
+    create_memory(text).map_err(|err| err.to_string())
+}
+
+fn parse(text: &str) -> Result<Case, String> {
+    let mut case = Case::default();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fail = |err: String| format!("line {}: {}", index + 1, err);
+        let (key, value) = match line.find(':') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => return Err(fail(format!("expected `key: value`, found {:?}", line))),
+        };
+        match key {
+            "program" => case.program.push(value.to_string()),
+            "input" => case.input = values(value).map_err(fail)?,
+            "output" => case.output = Some(values(value).map_err(fail)?),
+            "memory" => case.memory = Some(values(value).map_err(fail)?),
+            _ => return Err(fail(format!("unknown key {:?}", key))),
+        }
+    }
+    if case.program.is_empty() {
+        return Err("no program".to_string());
+    }
+    if case.output.is_none() {
+        return Err("no expected output".to_string());
+    }
+    Ok(case)
+}
+
+fn check(case: &Case) -> Result<(), String> {
+    let memory = create_memory(&case.program.join(",")).map_err(|err| err.to_string())?;
+    let mut machine = Machine::new(memory);
+    machine.limit_instructions(LIMIT);
+    for value in case.input.iter() {
+        machine.input(*value);
+    }
+    let mut output = Memory::new();
+    loop {
+        match machine.run_until_io().map_err(|err| err.to_string())? {
+            State::Output(value) => output.push(value),
+            State::Input => return Err(format!("waits for input after {:?}", output)),
+            State::Halted => break,
+        }
+    }
+    let expected = case.output.as_ref().expect("parse checks the output");
+    if output != *expected {
+        return Err(format!("output {:?}, expected {:?}", output, expected));
+    }
+    if let Some(expected) = case.memory.as_ref() {
+        let memory = machine.memory().range(0, expected.len());
+        if memory != *expected {
+            return Err(format!("memory {:?}, expected {:?}", memory, expected));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cases() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .expect("cannot read tests/cases")
+        .map(|entry| entry.expect("cannot read directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "case"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no cases in {}", dir.display());
+
+    let failures: Vec<String> = paths
+        .iter()
+        .filter_map(|path| {
+            let text = fs::read_to_string(path).expect("cannot read case");
+            parse(&text)
+                .and_then(|case| check(&case))
+                .err()
+                .map(|err| format!("{}: {}", path.display(), err))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} cases failed\n{}",
+        failures.len(),
+        paths.len(),
+        failures.join("\n")
+    );
+}
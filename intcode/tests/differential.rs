@@ -0,0 +1,394 @@
+//! Runs random programs on the day2, day5 and shared machines and compares
+//! their outputs and final memory. Divergent programs are shrunk to a small
+//! reproducer before the test fails.
+#[macro_use]
+extern crate log;
+extern crate intcode;
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use intcode::{Arithmetic, Machine, State};
+
+thread_local! {
+    static IO: RefCell<(VecDeque<i64>, Vec<i64>)> = const { RefCell::new((VecDeque::new(), Vec::new())) };
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+static HOOK: Once = Once::new();
+
+/// Runs `f` and reports whether it finished without panicking. The machines
+/// under test panic on faults, their messages are not printed.
+fn survives<F: FnOnce()>(f: F) -> bool {
+    HOOK.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                default(info)
+            }
+        }));
+    });
+    QUIET.with(|quiet| quiet.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    QUIET.with(|quiet| quiet.set(false));
+    result.is_ok()
+}
+
+fn next_input() -> i64 {
+    IO.with(|io| io.borrow_mut().0.pop_front().expect("no input left"))
+}
+
+fn emit(value: i64) {
+    IO.with(|io| io.borrow_mut().1.push(value))
+}
+
+// the day5 machine talks to the terminal, these route its I/O through `IO`
+macro_rules! scan {
+    ($fmt:expr, $var:ident) => {
+        $var = crate::next_input()
+    };
+}
+
+macro_rules! println {
+    ("enter value:") => {};
+    ("output: {}", $value:expr) => {
+        crate::emit($value)
+    };
+}
+
+#[allow(dead_code, unused_must_use, clippy::all)]
+#[path = "../../day2/src/intcode.rs"]
+mod day2;
+
+#[allow(dead_code, unused_must_use, clippy::all)]
+#[path = "../../day5/src/intcode.rs"]
+mod day5;
+
+/// How a program ended on one machine. Panics, errors and running into the
+/// instruction limit all count as a fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Done { outputs: Vec<i64>, memory: Vec<i64> },
+    Fault,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vm {
+    Day2,
+    Day5,
+    Shared,
+}
+
+const LIMIT: u64 = 10_000;
+
+fn run_day2(memory: &[i64]) -> Outcome {
+    let mut memory = memory.to_vec();
+    if !survives(|| {
+        day2::intcode(&mut memory);
+    }) {
+        return Outcome::Fault;
+    }
+    Outcome::Done {
+        outputs: Vec::new(),
+        memory,
+    }
+}
+
+fn run_day5(memory: &[i64], inputs: &[i64]) -> Outcome {
+    IO.with(|io| *io.borrow_mut() = (inputs.iter().copied().collect(), Vec::new()));
+    let mut memory = memory.to_vec();
+    let done = survives(|| day5::intcode(&mut memory));
+    let outputs = IO.with(|io| io.borrow_mut().1.split_off(0));
+    if !done {
+        return Outcome::Fault;
+    }
+    Outcome::Done { outputs, memory }
+}
+
+fn run_shared(memory: &[i64], inputs: &[i64]) -> Outcome {
+    let mut p = Machine::new(memory.to_vec());
+    // the other machines panic on overflow in debug builds and wrap in release
+    if cfg!(debug_assertions) {
+        p.arithmetic(Arithmetic::Checked);
+    }
+    p.limit_instructions(LIMIT);
+    for value in inputs {
+        p.input(*value);
+    }
+    let mut outputs = Vec::new();
+    loop {
+        match p.run_until_io() {
+            Ok(State::Output(value)) => outputs.push(value),
+            Ok(State::Halted) => break,
+            Ok(State::Input) | Err(_) => return Outcome::Fault,
+        }
+    }
+    let memory = p
+        .exit()
+        .expect("halted machine has an exit")
+        .memory
+        .to_vec();
+    Outcome::Done { outputs, memory }
+}
+
+/// An operand before layout, addresses are relative so that removing
+/// instructions keeps the program valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    Imm(i64),
+    /// A cell of the data block behind the code.
+    Data(usize),
+    /// A word of the code, read in position mode.
+    Code(usize),
+    /// Immediate jump target `n` instructions ahead, at most the final `HLT`.
+    Ahead(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Program {
+    code: Vec<(u8, Vec<Arg>)>,
+    data: Vec<i64>,
+    inputs: Vec<i64>,
+}
+
+impl Program {
+    /// Memory image: the instructions, a final `HLT`, then the data block.
+    fn layout(&self) -> Vec<i64> {
+        let mut starts = Vec::with_capacity(self.code.len() + 1);
+        let mut len = 0;
+        for (_, args) in self.code.iter() {
+            starts.push(len);
+            len += args.len() + 1;
+        }
+        starts.push(len);
+        let data = len + 1;
+        let mut memory = Vec::with_capacity(data + self.data.len());
+        for (i, (op, args)) in self.code.iter().enumerate() {
+            let mut code = *op as i64;
+            let mut scale = 100;
+            for arg in args.iter() {
+                if let Arg::Imm(_) | Arg::Ahead(_) = arg {
+                    code += scale;
+                }
+                scale *= 10;
+            }
+            memory.push(code);
+            for arg in args.iter() {
+                memory.push(match *arg {
+                    Arg::Imm(value) => value,
+                    Arg::Data(n) => (data + n % self.data.len()) as i64,
+                    Arg::Code(n) => (n % data) as i64,
+                    Arg::Ahead(n) => starts[(i + n).min(self.code.len())] as i64,
+                });
+            }
+        }
+        memory.push(99);
+        memory.extend(self.data.iter());
+        memory
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words: Vec<String> = self.layout().iter().map(|x| x.to_string()).collect();
+        write!(f, "program {} inputs {:?}", words.join(","), self.inputs)
+    }
+}
+
+/// Xorshift generator, the harness has to be reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn range(&mut self, from: i64, to: i64) -> i64 {
+        from + (self.next() % (to - from) as u64) as i64
+    }
+}
+
+/// Opcodes, parameter modes and machines of one family of programs.
+struct Subset {
+    ops: &'static [u8],
+    immediate: bool,
+    vms: &'static [Vm],
+}
+
+/// Only `ADD`, `MUL` and `HLT` in position mode.
+const DAY2: Subset = Subset {
+    ops: &[1, 2],
+    immediate: false,
+    vms: &[Vm::Day2, Vm::Day5, Vm::Shared],
+};
+
+/// Everything but `ARB`, no relative mode.
+const DAY5: Subset = Subset {
+    ops: &[1, 2, 3, 4, 5, 6, 7, 8],
+    immediate: true,
+    vms: &[Vm::Day5, Vm::Shared],
+};
+
+fn generate(rng: &mut Rng, subset: &Subset) -> Program {
+    let read = |rng: &mut Rng| match rng.below(4) {
+        0 if subset.immediate => Arg::Imm(rng.range(-50, 50)),
+        1 => Arg::Code(rng.below(64)),
+        _ => Arg::Data(rng.below(8)),
+    };
+    let mut code = Vec::new();
+    let mut inputs = Vec::new();
+    for _ in 0..1 + rng.below(16) {
+        let op = subset.ops[rng.below(subset.ops.len())];
+        let args = match op {
+            1 | 2 | 7 | 8 => vec![read(rng), read(rng), Arg::Data(rng.below(8))],
+            3 => {
+                inputs.push(rng.range(-100, 100));
+                vec![Arg::Data(rng.below(8))]
+            }
+            4 => vec![read(rng)],
+            5 | 6 => vec![read(rng), Arg::Ahead(1 + rng.below(4))],
+            _ => unreachable!(),
+        };
+        code.push((op, args));
+    }
+    let data = (0..1 + rng.below(8)).map(|_| rng.range(-20, 20)).collect();
+    Program { code, data, inputs }
+}
+
+fn run(program: &Program, vms: &[Vm]) -> Vec<(Vm, Outcome)> {
+    let memory = program.layout();
+    vms.iter()
+        .map(|vm| {
+            let outcome = match vm {
+                Vm::Day2 => run_day2(&memory),
+                Vm::Day5 => run_day5(&memory, &program.inputs),
+                Vm::Shared => run_shared(&memory, &program.inputs),
+            };
+            (*vm, outcome)
+        })
+        .collect()
+}
+
+fn diverges(program: &Program, vms: &[Vm]) -> bool {
+    let outcomes = run(program, vms);
+    outcomes
+        .iter()
+        .any(|(_, outcome)| *outcome != outcomes[0].1)
+}
+
+/// Simpler versions of `program`: one instruction or data cell less, or
+/// one operand, data cell or input closer to zero.
+fn candidates(program: &Program) -> Vec<Program> {
+    let mut out = Vec::new();
+    for i in 0..program.code.len() {
+        let mut p = program.clone();
+        let (op, _) = p.code.remove(i);
+        if op == 3 && !p.inputs.is_empty() {
+            p.inputs.pop();
+        }
+        out.push(p);
+    }
+    if program.data.len() > 1 {
+        let mut p = program.clone();
+        p.data.pop();
+        out.push(p);
+    }
+    let smaller = |x: i64| if x.abs() > 1 { x / 2 } else { 0 };
+    for i in 0..program.code.len() {
+        for j in 0..program.code[i].1.len() {
+            let simpler = match program.code[i].1[j] {
+                Arg::Imm(x) if x != 0 => Arg::Imm(smaller(x)),
+                Arg::Data(n) if n > 0 => Arg::Data(0),
+                Arg::Code(n) if n > 0 => Arg::Code(0),
+                Arg::Ahead(n) if n > 1 => Arg::Ahead(1),
+                _ => continue,
+            };
+            let mut p = program.clone();
+            p.code[i].1[j] = simpler;
+            out.push(p);
+        }
+    }
+    for i in 0..program.data.len() {
+        if program.data[i] != 0 {
+            let mut p = program.clone();
+            p.data[i] = smaller(p.data[i]);
+            out.push(p);
+        }
+    }
+    for i in 0..program.inputs.len() {
+        if program.inputs[i] != 0 {
+            let mut p = program.clone();
+            p.inputs[i] = smaller(p.inputs[i]);
+            out.push(p);
+        }
+    }
+    out
+}
+
+/// Greedily applies simplifications as long as `failing` still holds.
+fn minimize<F: Fn(&Program) -> bool>(mut program: Program, failing: F) -> Program {
+    while let Some(simpler) = candidates(&program).into_iter().find(|p| failing(p)) {
+        program = simpler;
+    }
+    program
+}
+
+fn check(subset: &Subset, seeds: u64) {
+    let mut failures = Vec::new();
+    for seed in 1..=seeds {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let program = generate(&mut rng, subset);
+        if diverges(&program, subset.vms) {
+            let small = minimize(program, |p| diverges(p, subset.vms));
+            failures.push(format!(
+                "seed {}: {}\n{:?}",
+                seed,
+                small,
+                run(&small, subset.vms)
+            ));
+            if failures.len() == 5 {
+                break;
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_day2_subset() {
+    check(&DAY2, 500);
+}
+
+#[test]
+fn test_day5_subset() {
+    check(&DAY5, 500);
+}
+
+#[test]
+fn test_minimize() {
+    // pretend every program that multiplies by an odd number diverges
+    let failing = |p: &Program| {
+        let memory = p.layout();
+        p.code.iter().any(|(op, _)| *op == 2) && memory.iter().any(|x| x % 2 != 0)
+    };
+    let mut rng = Rng(42);
+    let program = (0..)
+        .map(|_| generate(&mut rng, &DAY5))
+        .find(|p| p.code.len() > 8 && failing(p))
+        .unwrap();
+    let small = minimize(program, failing);
+    assert_eq!(small.code.len(), 1);
+    assert_eq!(small.code[0].0, 2);
+    assert!(small.layout().len() <= 7, "{}", small);
+    assert_eq!(run(&small, &[Vm::Shared]).len(), 1);
+}